@@ -0,0 +1,566 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::Key;
+
+/// Keys held together, e.g. `Alt+Shift+J`. Modifiers are kept in canonical order: Ctrl, Alt, Shift
+/// and Win, generic key before left and right one, so equal combos compare and print the same
+/// regardless of how they were written.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KeyCombo {
+    modifiers: Vec<Key>,
+    key: Key,
+}
+
+/// How side specific modifiers, like `Key::ShiftLeft`, are matched. Generic `Key::Shift`,
+/// `Key::Ctrl` and `Key::Menu` always match either side.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModifierMatching {
+    /// Every modifier matches either side, left Shift in a combo fires with right Shift as well.
+    #[default]
+    SideAgnostic,
+    /// Side specific modifiers match only their side.
+    Strict,
+}
+
+/// Logical modifier, both sides of a key belong to the same one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Modifier {
+    Ctrl,
+    Alt,
+    Shift,
+    Win,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseKeyComboError {
+    Empty,
+    UnknownKey(String),
+    /// Only the last key of a combo can be a non modifier key.
+    NotAModifier(String),
+    /// Same key written twice, e.g. `Ctrl+Control+J`.
+    DuplicateKey(String),
+    /// Separator with no key after it, e.g. `Ctrl+`.
+    MissingKey,
+}
+
+impl fmt::Display for ParseKeyComboError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "key combo is empty"),
+            Self::UnknownKey(name) => write!(f, "unknown key \"{}\"", name),
+            Self::NotAModifier(name) => write!(f, "\"{}\" is not a modifier", name),
+            Self::DuplicateKey(name) => write!(f, "\"{}\" appears more than once", name),
+            Self::MissingKey => write!(f, "key combo has an empty key"),
+        }
+    }
+}
+
+impl std::error::Error for ParseKeyComboError {}
+
+impl KeyCombo {
+    pub fn new(modifiers: &[Key], key: Key) -> Self {
+        let mut modifiers = modifiers.to_vec();
+        modifiers.sort_by_key(|k| modifier_rank(*k));
+        modifiers.dedup();
+        Self { modifiers, key }
+    }
+
+    /// Builds combo from set of held keys in any order, last non modifier key is the trigger.
+    pub fn from_keys(keys: &[Key]) -> Option<Self> {
+        let key_idx = keys
+            .iter()
+            .rposition(|k| !k.is_modifier())
+            .or(keys.len().checked_sub(1))?;
+
+        let mut modifiers = keys.to_vec();
+        let key = modifiers.remove(key_idx);
+        Some(Self::new(&modifiers, key))
+    }
+
+    pub fn modifiers(&self) -> &[Key] {
+        &self.modifiers
+    }
+
+    pub fn key(&self) -> Key {
+        self.key
+    }
+
+    /// Checks whether `pressed` keys trigger this combo. Held modifiers have to be exactly the
+    /// modifiers of this combo while other held keys are ignored, so a stuck key does not break
+    /// bindings.
+    pub fn matches(&self, pressed: &[Key], matching: ModifierMatching) -> bool {
+        let Some(trigger_idx) = pressed
+            .iter()
+            .position(|k| key_matches(self.key, *k, matching))
+        else {
+            return false;
+        };
+
+        let others: Vec<Key> = pressed
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != trigger_idx)
+            .map(|(_, k)| *k)
+            .collect();
+        modifiers_match(&self.modifiers, &others, matching)
+    }
+
+    /// Same as `matches` but also requires `key`, the one that just went down, to be the one
+    /// completing this combo. Otherwise pressing Shift while J is held would fire `Shift+J`. In
+    /// combos made of modifiers alone any of them can go down last.
+    pub fn triggered_by(&self, key: Key, pressed: &[Key], matching: ModifierMatching) -> bool {
+        let completes = if self.key.is_modifier() {
+            self.keys().iter().any(|k| key_matches(*k, key, matching))
+        } else {
+            key_matches(self.key, key, matching)
+        };
+        completes && self.matches(pressed, matching)
+    }
+
+    /// Checks whether some set of pressed keys triggers both combos.
+    pub fn overlaps(&self, other: &Self, matching: ModifierMatching) -> bool {
+        let keys_overlap =
+            |a: Key, b: Key| key_matches(a, b, matching) || key_matches(b, a, matching);
+        let covers = |a: &Self, b: &Self| {
+            a.modifiers
+                .iter()
+                .all(|m| b.modifiers.iter().any(|o| keys_overlap(*m, *o)))
+        };
+
+        keys_overlap(self.key, other.key) && covers(self, other) && covers(other, self)
+    }
+
+    /// All keys of this combo, modifiers first.
+    pub fn keys(&self) -> Vec<Key> {
+        let mut keys = self.modifiers.clone();
+        keys.push(self.key);
+        keys
+    }
+}
+
+impl FromStr for KeyCombo {
+    type Err = ParseKeyComboError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(ParseKeyComboError::Empty);
+        }
+        if s == "+" {
+            return Ok(Self::new(&[], Key::Plus));
+        }
+
+        // `+` on its own is the key after the last separator, e.g. "Ctrl++".
+        let (rest, last) = match s.strip_suffix("++") {
+            Some(rest) => (Some(rest), "+"),
+            None => match s.rsplit_once('+') {
+                Some((rest, last)) => (Some(rest), last),
+                None => (None, s),
+            },
+        };
+
+        let parse = |name: &str| match name.trim() {
+            "" => Err(ParseKeyComboError::MissingKey),
+            name => name.parse::<Key>(),
+        };
+
+        let key = parse(last)?;
+        let mut modifiers: Vec<Key> = Vec::new();
+        for name in rest.into_iter().flat_map(|r| r.split('+')) {
+            let modifier = parse(name)?;
+            if !modifier.is_modifier() {
+                return Err(ParseKeyComboError::NotAModifier(name.trim().to_string()));
+            }
+            if modifier == key || modifiers.contains(&modifier) {
+                return Err(ParseKeyComboError::DuplicateKey(name.trim().to_string()));
+            }
+            modifiers.push(modifier);
+        }
+
+        Ok(Self::new(&modifiers, key))
+    }
+}
+
+impl fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for modifier in &self.modifiers {
+            write!(f, "{}+", modifier)?;
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+// Names used instead of variant names when printing, parsing accepts both.
+const KEY_NAMES: &[(&str, Key)] = &[
+    ("LCtrl", Key::CtrlLeft),
+    ("RCtrl", Key::CtrlRight),
+    ("Alt", Key::Menu),
+    ("LAlt", Key::AltLeft),
+    ("RAlt", Key::AltRight),
+    ("LShift", Key::ShiftLeft),
+    ("RShift", Key::ShiftRight),
+    ("Super", Key::Win),
+    ("RSuper", Key::WinR),
+    ("0", Key::N0),
+    ("1", Key::N1),
+    ("2", Key::N2),
+    ("3", Key::N3),
+    ("4", Key::N4),
+    ("5", Key::N5),
+    ("6", Key::N6),
+    ("7", Key::N7),
+    ("8", Key::N8),
+    ("9", Key::N9),
+];
+
+// Additional names accepted when parsing.
+const KEY_ALIASES: &[(&str, Key)] = &[
+    ("Control", Key::Ctrl),
+    ("AltGr", Key::AltRight),
+    ("LSuper", Key::Win),
+    ("LWin", Key::Win),
+    ("RWin", Key::WinR),
+    ("Backspace", Key::Back),
+    ("Enter", Key::Return),
+    ("Escape", Key::Esc),
+    ("+", Key::Plus),
+    (",", Key::Comma),
+    ("-", Key::Minus),
+    (".", Key::Period),
+    ("\\", Key::BackSlash),
+];
+
+impl Key {
+    pub fn is_modifier(&self) -> bool {
+        self.modifier().is_some()
+    }
+
+    pub fn modifier(&self) -> Option<Modifier> {
+        match self {
+            Key::Ctrl | Key::CtrlLeft | Key::CtrlRight => Some(Modifier::Ctrl),
+            Key::Menu | Key::AltLeft | Key::AltRight => Some(Modifier::Alt),
+            Key::Shift | Key::ShiftLeft | Key::ShiftRight => Some(Modifier::Shift),
+            Key::Win | Key::WinR => Some(Modifier::Win),
+            _ => None,
+        }
+    }
+
+    // Windows has no generic Win key, `Key::Win` is the left one.
+    fn is_generic_modifier(&self) -> bool {
+        matches!(self, Key::Ctrl | Key::Menu | Key::Shift)
+    }
+}
+
+/// Checks whether modifiers held among `pressed` keys are exactly `modifiers`, other held keys are
+/// ignored. Used for mouse bindings, which have modifiers but no trigger key.
+pub fn modifiers_match(modifiers: &[Key], pressed: &[Key], matching: ModifierMatching) -> bool {
+    let held_modifiers: Vec<Key> = pressed.iter().copied().filter(Key::is_modifier).collect();
+
+    held_modifiers
+        .iter()
+        .all(|held| modifiers.iter().any(|m| key_matches(*m, *held, matching)))
+        && modifiers.iter().all(|m| {
+            held_modifiers
+                .iter()
+                .any(|held| key_matches(*m, *held, matching))
+        })
+}
+
+// Checks whether key `bound` in a combo is satisfied by `held` key.
+fn key_matches(bound: Key, held: Key, matching: ModifierMatching) -> bool {
+    if bound == held {
+        return true;
+    }
+
+    match (bound.modifier(), held.modifier()) {
+        (Some(a), Some(b)) if a == b => {
+            matching == ModifierMatching::SideAgnostic || bound.is_generic_modifier()
+        }
+        _ => false,
+    }
+}
+
+// Position of modifier in canonical order, `usize::MAX` for other keys.
+fn modifier_rank(key: Key) -> usize {
+    const ORDER: [Key; 11] = [
+        Key::Ctrl,
+        Key::CtrlLeft,
+        Key::CtrlRight,
+        Key::Menu,
+        Key::AltLeft,
+        Key::AltRight,
+        Key::Shift,
+        Key::ShiftLeft,
+        Key::ShiftRight,
+        Key::Win,
+        Key::WinR,
+    ];
+    ORDER.iter().position(|k| *k == key).unwrap_or(usize::MAX)
+}
+
+impl FromStr for Key {
+    type Err = ParseKeyComboError;
+
+    /// Accepts variant names and common aliases like `LCtrl` or `Super`, case insensitive.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim();
+        if name.is_empty() {
+            return Err(ParseKeyComboError::Empty);
+        }
+
+        if let Some((_, key)) = KEY_NAMES
+            .iter()
+            .chain(KEY_ALIASES)
+            .find(|(alias, _)| alias.eq_ignore_ascii_case(name))
+        {
+            return Ok(*key);
+        }
+
+        // Keys without a name are written as their virtual key code, e.g. `0x07`.
+        if let Some(code) = name
+            .strip_prefix("0x")
+            .or_else(|| name.strip_prefix("0X"))
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            return Ok(Key::from_vk_code(code));
+        }
+
+        (0..=u8::MAX)
+            .map(Key::from_vk_code)
+            .filter(|k| !matches!(k, Key::Unknown(_)))
+            .find(|k| format!("{:?}", k).eq_ignore_ascii_case(name))
+            .ok_or_else(|| ParseKeyComboError::UnknownKey(name.to_string()))
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Key::Unknown(code) = self {
+            return write!(f, "{:#04x}", code);
+        }
+
+        match KEY_NAMES.iter().find(|(_, key)| key == self) {
+            Some((alias, _)) => write!(f, "{}", alias),
+            None => write!(f, "{:?}", self),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_keys() -> impl Iterator<Item = Key> {
+        (0..=u8::MAX).map(Key::from_vk_code)
+    }
+
+    #[test]
+    fn every_key_round_trips() {
+        for key in all_keys() {
+            let name = key.to_string();
+            assert_eq!(name.parse::<Key>(), Ok(key), "{}", name);
+        }
+    }
+
+    #[test]
+    fn every_key_round_trips_as_combo() {
+        for key in all_keys() {
+            let combo = KeyCombo::new(&[Key::Ctrl, Key::ShiftLeft], key);
+            if combo.modifiers().contains(&key) {
+                continue;
+            }
+            let text = combo.to_string();
+            assert_eq!(text.parse::<KeyCombo>(), Ok(combo), "{}", text);
+        }
+    }
+
+    #[test]
+    fn named_keys_print_their_name() {
+        for (name, key) in KEY_NAMES {
+            assert_eq!(key.to_string(), *name);
+        }
+        assert_eq!(Key::Back.to_string(), "Back");
+        assert_eq!(Key::Unknown(0x07).to_string(), "0x07");
+    }
+
+    #[test]
+    fn variant_names_are_accepted() {
+        assert_eq!("Menu".parse::<Key>(), Ok(Key::Menu));
+        assert_eq!("CtrlLeft".parse::<Key>(), Ok(Key::CtrlLeft));
+        assert_eq!("N3".parse::<Key>(), Ok(Key::N3));
+        assert_eq!("0x07".parse::<Key>(), Ok(Key::Unknown(0x07)));
+        assert_eq!("0X07".parse::<Key>(), Ok(Key::Unknown(0x07)));
+    }
+
+    #[test]
+    fn aliases_are_accepted() {
+        for (alias, key) in KEY_ALIASES {
+            assert_eq!(alias.parse::<Key>(), Ok(*key), "{}", alias);
+        }
+    }
+
+    #[test]
+    fn names_are_case_insensitive() {
+        for key in all_keys() {
+            let name = key.to_string();
+            assert_eq!(name.to_lowercase().parse::<Key>(), Ok(key), "{}", name);
+            assert_eq!(name.to_uppercase().parse::<Key>(), Ok(key), "{}", name);
+        }
+        for (alias, key) in KEY_ALIASES {
+            assert_eq!(alias.to_lowercase().parse::<Key>(), Ok(*key));
+        }
+        assert_eq!(
+            "ctrl+SHIFT+j".parse(),
+            Ok(KeyCombo::new(&[Key::Ctrl, Key::Shift], Key::J))
+        );
+    }
+
+    #[test]
+    fn combo_prints_in_canonical_order() {
+        let combo: KeyCombo = " Super + shift+LAlt+control +J ".parse().unwrap();
+        assert_eq!(combo.to_string(), "Ctrl+LAlt+Shift+Super+J");
+        assert_eq!(
+            combo,
+            KeyCombo::new(&[Key::Win, Key::Shift, Key::AltLeft, Key::Ctrl], Key::J)
+        );
+    }
+
+    #[test]
+    fn plus_key() {
+        assert_eq!("+".parse(), Ok(KeyCombo::new(&[], Key::Plus)));
+        assert_eq!("Ctrl++".parse(), Ok(KeyCombo::new(&[Key::Ctrl], Key::Plus)));
+        assert_eq!(
+            "Ctrl+Plus".parse(),
+            Ok(KeyCombo::new(&[Key::Ctrl], Key::Plus))
+        );
+    }
+
+    #[test]
+    fn modifier_alone_is_a_combo() {
+        assert_eq!(
+            "Ctrl+LAlt".parse(),
+            Ok(KeyCombo::new(&[Key::Ctrl], Key::AltLeft))
+        );
+        assert_eq!("Super".parse(), Ok(KeyCombo::new(&[], Key::Win)));
+    }
+
+    #[test]
+    fn empty_combo() {
+        assert_eq!("".parse::<KeyCombo>(), Err(ParseKeyComboError::Empty));
+        assert_eq!("  ".parse::<KeyCombo>(), Err(ParseKeyComboError::Empty));
+        assert_eq!("".parse::<Key>(), Err(ParseKeyComboError::Empty));
+    }
+
+    #[test]
+    fn unknown_key() {
+        assert_eq!(
+            "Ctrl+Foo".parse::<KeyCombo>(),
+            Err(ParseKeyComboError::UnknownKey("Foo".to_string()))
+        );
+        assert_eq!(
+            "Foo+J".parse::<KeyCombo>(),
+            Err(ParseKeyComboError::UnknownKey("Foo".to_string()))
+        );
+        assert_eq!(
+            "0x".parse::<Key>(),
+            Err(ParseKeyComboError::UnknownKey("0x".to_string()))
+        );
+        assert_eq!(
+            "Unknown".parse::<Key>(),
+            Err(ParseKeyComboError::UnknownKey("Unknown".to_string()))
+        );
+    }
+
+    #[test]
+    fn duplicate_key() {
+        assert_eq!(
+            "Ctrl+Ctrl+J".parse::<KeyCombo>(),
+            Err(ParseKeyComboError::DuplicateKey("Ctrl".to_string()))
+        );
+        assert_eq!(
+            "Ctrl+Control+J".parse::<KeyCombo>(),
+            Err(ParseKeyComboError::DuplicateKey("Control".to_string()))
+        );
+        assert_eq!(
+            "Shift+Shift".parse::<KeyCombo>(),
+            Err(ParseKeyComboError::DuplicateKey("Shift".to_string()))
+        );
+    }
+
+    #[test]
+    fn missing_key() {
+        assert_eq!(
+            "Ctrl+".parse::<KeyCombo>(),
+            Err(ParseKeyComboError::MissingKey)
+        );
+        assert_eq!(
+            "Ctrl+ ".parse::<KeyCombo>(),
+            Err(ParseKeyComboError::MissingKey)
+        );
+        assert_eq!(
+            "+J".parse::<KeyCombo>(),
+            Err(ParseKeyComboError::MissingKey)
+        );
+        assert_eq!(
+            "Ctrl++J".parse::<KeyCombo>(),
+            Err(ParseKeyComboError::MissingKey)
+        );
+    }
+
+    fn combo(s: &str) -> KeyCombo {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn matches_held_keys() {
+        let alt_j = combo("Alt+J");
+        let m = ModifierMatching::SideAgnostic;
+        assert!(alt_j.matches(&[Key::AltLeft, Key::J], m));
+        assert!(alt_j.matches(&[Key::J, Key::AltRight], m));
+        assert!(alt_j.matches(&[Key::AltLeft, Key::K, Key::J], m));
+        assert!(!alt_j.matches(&[Key::J], m));
+        assert!(!alt_j.matches(&[Key::AltLeft, Key::ShiftLeft, Key::J], m));
+    }
+
+    #[test]
+    fn side_specific_modifiers() {
+        let lalt_j = combo("LAlt+J");
+        assert!(lalt_j.matches(&[Key::AltRight, Key::J], ModifierMatching::SideAgnostic));
+        assert!(!lalt_j.matches(&[Key::AltRight, Key::J], ModifierMatching::Strict));
+        assert!(lalt_j.matches(&[Key::AltLeft, Key::J], ModifierMatching::Strict));
+        assert!(combo("Alt+J").matches(&[Key::AltRight, Key::J], ModifierMatching::Strict));
+    }
+
+    #[test]
+    fn triggered_only_by_key_completing_combo() {
+        let m = ModifierMatching::SideAgnostic;
+        let pressed = [Key::J, Key::ShiftLeft];
+        assert!(!combo("Shift+J").triggered_by(Key::ShiftLeft, &pressed, m));
+        assert!(combo("Shift+J").triggered_by(Key::J, &pressed, m));
+        assert!(!combo("Shift+J").triggered_by(Key::K, &[Key::ShiftLeft, Key::J, Key::K], m));
+        assert!(!combo("J").triggered_by(Key::J, &pressed, m));
+    }
+
+    #[test]
+    fn modifier_only_combo_is_triggered_by_any_of_its_keys() {
+        let m = ModifierMatching::SideAgnostic;
+        let pressed = [Key::CtrlLeft, Key::AltLeft];
+        assert!(combo("Ctrl+Alt").triggered_by(Key::AltLeft, &pressed, m));
+        assert!(combo("Ctrl+Alt").triggered_by(Key::CtrlLeft, &pressed, m));
+        assert!(!combo("Ctrl+Alt").triggered_by(Key::J, &[Key::CtrlLeft, Key::AltLeft, Key::J], m));
+        assert!(!combo("LCtrl+Alt").triggered_by(
+            Key::CtrlRight,
+            &pressed,
+            ModifierMatching::Strict
+        ));
+    }
+
+    #[test]
+    fn non_modifier_before_last_key() {
+        assert_eq!(
+            "J+K".parse::<KeyCombo>(),
+            Err(ParseKeyComboError::NotAModifier("J".to_string()))
+        );
+    }
+}
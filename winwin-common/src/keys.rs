@@ -3,6 +3,12 @@ use serde::{Serialize, Deserialize};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Key {
+    LButton,
+    RButton,
+    Cancel,
+    MButton,
+    XButton1,
+    XButton2,
     Back,
     Tab,
     Clear,
@@ -12,7 +18,17 @@ pub enum Key {
     Menu,
     Pause,
     CapsLock,
+    Kana,
+    ImeOn,
+    Junja,
+    Final,
+    Kanji,
+    ImeOff,
     Esc,
+    Convert,
+    NonConvert,
+    Accept,
+    ModeChange,
     Space,
     PageUp,
     PageDown,
@@ -67,6 +83,8 @@ pub enum Key {
     Z,
     Win,
     WinR,
+    Apps,
+    Sleep,
     Numpad0,
     Numpad1,
     Numpad2,
@@ -115,19 +133,62 @@ pub enum Key {
     CtrlRight,
     AltLeft,
     AltRight,
+    BrowserBack,
+    BrowserForward,
+    BrowserRefresh,
+    BrowserStop,
+    BrowserSearch,
+    BrowserFavorites,
+    BrowserHome,
+    VolumeMute,
+    VolumeDown,
+    VolumeUp,
+    MediaNextTrack,
+    MediaPrevTrack,
+    MediaStop,
+    MediaPlayPause,
+    LaunchMail,
+    LaunchMediaSelect,
+    LaunchApp1,
+    LaunchApp2,
+    Semicolon,
     Plus,
     Comma,
     Minus,
     Period,
-
+    Slash,
+    Grave,
+    BracketLeft,
     BackSlash,
+    BracketRight,
+    Quote,
+    Oem8,
+    Oem102,
+    ProcessKey,
+    Packet,
+    Attn,
+    CrSel,
+    ExSel,
+    EraseEof,
+    Play,
+    Zoom,
+    Pa1,
+    OemClear,
 
-    Unknown,
+    /// Codes that are reserved, unassigned or OEM specific. Raw code is kept so these can still be
+    /// bound.
+    Unknown(u8),
 }
 
 impl Key {
     pub fn from_vk_code(code: u8) -> Self {
         match code {
+            0x01 => Self::LButton,
+            0x02 => Self::RButton,
+            0x03 => Self::Cancel,
+            0x04 => Self::MButton,
+            0x05 => Self::XButton1,
+            0x06 => Self::XButton2,
             0x08 => Self::Back,
             0x09 => Self::Tab,
             0x0C => Self::Clear,
@@ -137,7 +198,17 @@ impl Key {
             0x12 => Self::Menu,
             0x13 => Self::Pause,
             0x14 => Self::CapsLock,
+            0x15 => Self::Kana,
+            0x16 => Self::ImeOn,
+            0x17 => Self::Junja,
+            0x18 => Self::Final,
+            0x19 => Self::Kanji,
+            0x1A => Self::ImeOff,
             0x1B => Self::Esc,
+            0x1C => Self::Convert,
+            0x1D => Self::NonConvert,
+            0x1E => Self::Accept,
+            0x1F => Self::ModeChange,
             0x20 => Self::Space,
             0x21 => Self::PageUp,
             0x22 => Self::PageDown,
@@ -192,6 +263,8 @@ impl Key {
             0x5A => Self::Z,
             0x5B => Self::Win,
             0x5C => Self::WinR,
+            0x5D => Self::Apps,
+            0x5F => Self::Sleep,
             0x60 => Self::Numpad0,
             0x61 => Self::Numpad1,
             0x62 => Self::Numpad2,
@@ -240,17 +313,230 @@ impl Key {
             0xA3 => Self::CtrlRight,
             0xA4 => Self::AltLeft,
             0xA5 => Self::AltRight,
+            0xA6 => Self::BrowserBack,
+            0xA7 => Self::BrowserForward,
+            0xA8 => Self::BrowserRefresh,
+            0xA9 => Self::BrowserStop,
+            0xAA => Self::BrowserSearch,
+            0xAB => Self::BrowserFavorites,
+            0xAC => Self::BrowserHome,
+            0xAD => Self::VolumeMute,
+            0xAE => Self::VolumeDown,
+            0xAF => Self::VolumeUp,
+            0xB0 => Self::MediaNextTrack,
+            0xB1 => Self::MediaPrevTrack,
+            0xB2 => Self::MediaStop,
+            0xB3 => Self::MediaPlayPause,
+            0xB4 => Self::LaunchMail,
+            0xB5 => Self::LaunchMediaSelect,
+            0xB6 => Self::LaunchApp1,
+            0xB7 => Self::LaunchApp2,
+            0xBA => Self::Semicolon,
             0xBB => Self::Plus,
             0xBC => Self::Comma,
             0xBD => Self::Minus,
             0xBE => Self::Period,
+            0xBF => Self::Slash,
+            0xC0 => Self::Grave,
+            0xDB => Self::BracketLeft,
             0xDC => Self::BackSlash,
-            _ => Self::Unknown,
+            0xDD => Self::BracketRight,
+            0xDE => Self::Quote,
+            0xDF => Self::Oem8,
+            0xE2 => Self::Oem102,
+            0xE5 => Self::ProcessKey,
+            0xE7 => Self::Packet,
+            0xF6 => Self::Attn,
+            0xF7 => Self::CrSel,
+            0xF8 => Self::ExSel,
+            0xF9 => Self::EraseEof,
+            0xFA => Self::Play,
+            0xFB => Self::Zoom,
+            0xFD => Self::Pa1,
+            0xFE => Self::OemClear,
+            _ => Self::Unknown(code),
+        }
+    }
+
+    pub fn to_vk_code(&self) -> u8 {
+        match self {
+            Self::LButton => 0x01,
+            Self::RButton => 0x02,
+            Self::Cancel => 0x03,
+            Self::MButton => 0x04,
+            Self::XButton1 => 0x05,
+            Self::XButton2 => 0x06,
+            Self::Back => 0x08,
+            Self::Tab => 0x09,
+            Self::Clear => 0x0C,
+            Self::Return => 0x0D,
+            Self::Shift => 0x10,
+            Self::Ctrl => 0x11,
+            Self::Menu => 0x12,
+            Self::Pause => 0x13,
+            Self::CapsLock => 0x14,
+            Self::Kana => 0x15,
+            Self::ImeOn => 0x16,
+            Self::Junja => 0x17,
+            Self::Final => 0x18,
+            Self::Kanji => 0x19,
+            Self::ImeOff => 0x1A,
+            Self::Esc => 0x1B,
+            Self::Convert => 0x1C,
+            Self::NonConvert => 0x1D,
+            Self::Accept => 0x1E,
+            Self::ModeChange => 0x1F,
+            Self::Space => 0x20,
+            Self::PageUp => 0x21,
+            Self::PageDown => 0x22,
+            Self::End => 0x23,
+            Self::Home => 0x24,
+            Self::Left => 0x25,
+            Self::Up => 0x26,
+            Self::Right => 0x27,
+            Self::Down => 0x28,
+            Self::Select => 0x29,
+            Self::Print => 0x2A,
+            Self::Execute => 0x2B,
+            Self::PrintScreen => 0x2C,
+            Self::Insert => 0x2D,
+            Self::Delete => 0x2E,
+            Self::Help => 0x2F,
+            Self::N0 => 0x30,
+            Self::N1 => 0x31,
+            Self::N2 => 0x32,
+            Self::N3 => 0x33,
+            Self::N4 => 0x34,
+            Self::N5 => 0x35,
+            Self::N6 => 0x36,
+            Self::N7 => 0x37,
+            Self::N8 => 0x38,
+            Self::N9 => 0x39,
+            Self::A => 0x41,
+            Self::B => 0x42,
+            Self::C => 0x43,
+            Self::D => 0x44,
+            Self::E => 0x45,
+            Self::F => 0x46,
+            Self::G => 0x47,
+            Self::H => 0x48,
+            Self::I => 0x49,
+            Self::J => 0x4A,
+            Self::K => 0x4B,
+            Self::L => 0x4C,
+            Self::M => 0x4D,
+            Self::N => 0x4E,
+            Self::O => 0x4F,
+            Self::P => 0x50,
+            Self::Q => 0x51,
+            Self::R => 0x52,
+            Self::S => 0x53,
+            Self::T => 0x54,
+            Self::U => 0x55,
+            Self::V => 0x56,
+            Self::W => 0x57,
+            Self::X => 0x58,
+            Self::Y => 0x59,
+            Self::Z => 0x5A,
+            Self::Win => 0x5B,
+            Self::WinR => 0x5C,
+            Self::Apps => 0x5D,
+            Self::Sleep => 0x5F,
+            Self::Numpad0 => 0x60,
+            Self::Numpad1 => 0x61,
+            Self::Numpad2 => 0x62,
+            Self::Numpad3 => 0x63,
+            Self::Numpad4 => 0x64,
+            Self::Numpad5 => 0x65,
+            Self::Numpad6 => 0x66,
+            Self::Numpad7 => 0x67,
+            Self::Numpad8 => 0x68,
+            Self::Numpad9 => 0x69,
+            Self::Multiply => 0x6A,
+            Self::Add => 0x6B,
+            Self::Spectator => 0x6C,
+            Self::Subtract => 0x6D,
+            Self::Decimal => 0x6E,
+            Self::Devide => 0x6F,
+            Self::F1 => 0x70,
+            Self::F2 => 0x71,
+            Self::F3 => 0x72,
+            Self::F4 => 0x73,
+            Self::F5 => 0x74,
+            Self::F6 => 0x75,
+            Self::F7 => 0x76,
+            Self::F8 => 0x77,
+            Self::F9 => 0x78,
+            Self::F10 => 0x79,
+            Self::F11 => 0x7A,
+            Self::F12 => 0x7B,
+            Self::F13 => 0x7C,
+            Self::F14 => 0x7D,
+            Self::F15 => 0x7E,
+            Self::F16 => 0x7F,
+            Self::F17 => 0x80,
+            Self::F18 => 0x81,
+            Self::F19 => 0x82,
+            Self::F20 => 0x83,
+            Self::F21 => 0x84,
+            Self::F22 => 0x85,
+            Self::F23 => 0x86,
+            Self::F24 => 0x87,
+            Self::NumLock => 0x90,
+            Self::ScrollLock => 0x91,
+            Self::ShiftLeft => 0xA0,
+            Self::ShiftRight => 0xA1,
+            Self::CtrlLeft => 0xA2,
+            Self::CtrlRight => 0xA3,
+            Self::AltLeft => 0xA4,
+            Self::AltRight => 0xA5,
+            Self::BrowserBack => 0xA6,
+            Self::BrowserForward => 0xA7,
+            Self::BrowserRefresh => 0xA8,
+            Self::BrowserStop => 0xA9,
+            Self::BrowserSearch => 0xAA,
+            Self::BrowserFavorites => 0xAB,
+            Self::BrowserHome => 0xAC,
+            Self::VolumeMute => 0xAD,
+            Self::VolumeDown => 0xAE,
+            Self::VolumeUp => 0xAF,
+            Self::MediaNextTrack => 0xB0,
+            Self::MediaPrevTrack => 0xB1,
+            Self::MediaStop => 0xB2,
+            Self::MediaPlayPause => 0xB3,
+            Self::LaunchMail => 0xB4,
+            Self::LaunchMediaSelect => 0xB5,
+            Self::LaunchApp1 => 0xB6,
+            Self::LaunchApp2 => 0xB7,
+            Self::Semicolon => 0xBA,
+            Self::Plus => 0xBB,
+            Self::Comma => 0xBC,
+            Self::Minus => 0xBD,
+            Self::Period => 0xBE,
+            Self::Slash => 0xBF,
+            Self::Grave => 0xC0,
+            Self::BracketLeft => 0xDB,
+            Self::BackSlash => 0xDC,
+            Self::BracketRight => 0xDD,
+            Self::Quote => 0xDE,
+            Self::Oem8 => 0xDF,
+            Self::Oem102 => 0xE2,
+            Self::ProcessKey => 0xE5,
+            Self::Packet => 0xE7,
+            Self::Attn => 0xF6,
+            Self::CrSel => 0xF7,
+            Self::ExSel => 0xF8,
+            Self::EraseEof => 0xF9,
+            Self::Play => 0xFA,
+            Self::Zoom => 0xFB,
+            Self::Pa1 => 0xFD,
+            Self::OemClear => 0xFE,
+            Self::Unknown(code) => *code,
         }
     }
 }
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum KeyState {
     Up,
     Down,
@@ -278,3 +564,15 @@ impl From<WPARAM> for KeyState {
         unreachable!();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vk_code_round_trips() {
+        for vk_code in 0..=u8::MAX {
+            assert_eq!(Key::from_vk_code(vk_code).to_vk_code(), vk_code, "{:#04x}", vk_code);
+        }
+    }
+}
@@ -7,6 +7,12 @@ use windows::Win32::{Foundation::*, UI::WindowsAndMessaging::*};
 mod keys;
 pub use keys::*;
 
+mod combo;
+pub use combo::*;
+
+mod window;
+pub use window::*;
+
 /// This HANDLE must be safe to use from multiple threads.
 #[derive(Copy, Clone, Debug)]
 pub struct SyncHandle(pub HANDLE);
@@ -26,17 +32,60 @@ impl Deref for SyncHandle {
 pub enum ClientEvent {
     WindowOpen(usize, usize),
     WindowClose(usize, usize),
-    Keyboard(KBDelta),
+    /// Keystroke and its sequence number, reply to it has to carry the same number.
+    Keyboard(KBDelta, u64),
     WindowMonitorChanged(usize, usize),
     WindowFocusHanged(usize),
+    WindowTitleChanged(usize),
+    WindowMinimized(usize),
+    WindowRestored(usize),
+    WindowMoveSizeStart(usize),
+    WindowMoveSizeEnd(usize),
+    WindowHovered(usize),
+    /// Mouse button and its sequence number, reply to it has to carry the same number.
+    MouseButton(MouseDelta, u64),
+    /// Wheel rotation and its sequence number, replied to like `MouseButton`.
+    MouseWheel(WheelDelta, u64),
+    /// Cursor position, reported only while a drag started with intercepted button is going on.
+    MouseMove(Point),
     MonitorConnected(usize),
     MonitorDisconnected(usize),
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct KBDelta {
     pub vk_code: u8,
     pub key_state: KeyState,
+    /// Milliseconds since system start, wraps around every 49.7 days.
+    pub time: u32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    X1,
+    X2,
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct MouseDelta {
+    pub button: MouseButton,
+    pub button_state: KeyState,
+    pub point: Point,
+    /// Milliseconds since system start, same clock as `KBDelta::time`.
+    pub time: u32,
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct WheelDelta {
+    /// Rotation in multiples of `WHEEL_DELTA` (120) per notch, high resolution wheels report
+    /// smaller steps. Positive is away from the user or to the right.
+    pub delta: i16,
+    pub horizontal: bool,
+    pub point: Point,
+    pub time: u32,
 }
 
 #[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
@@ -104,6 +153,28 @@ impl Rect {
         }
     }
 
+    pub fn contains(&self, point: Point) -> bool {
+        point.x >= self.x
+            && point.x < self.x + self.width
+            && point.y >= self.y
+            && point.y < self.y + self.height
+    }
+
+    /// Smallest rect containing both rects.
+    pub fn union(&self, other: &Self) -> Self {
+        let x1 = self.x.min(other.x);
+        let y1 = self.y.min(other.y);
+        let x2 = (self.x + self.width).max(other.x + other.width);
+        let y2 = (self.y + self.height).max(other.y + other.height);
+
+        Self {
+            x: x1,
+            y: y1,
+            width: x2 - x1,
+            height: y2 - y1,
+        }
+    }
+
     pub fn center(&self) -> Point {
         let x = self.x + self.width / 2;
         let y = self.y + self.height / 2;
@@ -120,12 +191,18 @@ impl Rect {
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Point {
     pub x: i32,
     pub y: i32,
 }
 
+impl From<POINT> for Point {
+    fn from(p: POINT) -> Self {
+        Self { x: p.x, y: p.y }
+    }
+}
+
 impl Point {
     pub fn distance(&self, other: Self) -> i32 {
         ((self.x - other.x).pow(2) as f32 + (self.y - other.y).pow(2) as f32).sqrt() as i32
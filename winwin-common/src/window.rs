@@ -0,0 +1,338 @@
+use windows::Win32::{Foundation::*, Graphics::Dwm::*, UI::WindowsAndMessaging::*};
+
+use crate::Rect;
+
+/// Plain snapshot of window properties used for classification. It holds no live handles so the
+/// same rules can run inside the hooks dll and in the main process.
+#[derive(Debug, Clone, Default)]
+pub struct WindowDescriptor {
+    pub style: WINDOW_STYLE,
+    pub ex_style: WINDOW_EX_STYLE,
+    pub rect: Rect,
+    pub title: String,
+    /// Handle of the owner window, 0 if window has no owner.
+    pub owner: usize,
+    /// UWP apps and windows on other virtual desktops are cloaked, they are "visible" but not shown.
+    pub cloaked: bool,
+}
+
+impl WindowDescriptor {
+    /// Queries all descriptor fields for `handle`. Failing calls leave their fields defaulted,
+    /// this never panics so it can be called from the hooks dll.
+    pub fn query(handle: HWND) -> Self {
+        unsafe {
+            let style = WINDOW_STYLE(GetWindowLongW(handle, GWL_STYLE) as _);
+            let ex_style = WINDOW_EX_STYLE(GetWindowLongW(handle, GWL_EXSTYLE) as _);
+
+            let mut r = RECT::default();
+            let _ = GetWindowRect(handle, &mut r as *mut _);
+
+            let mut buff = [0; 512];
+            let end = GetWindowTextW(handle, &mut buff).max(0);
+            let title = String::from_utf16_lossy(&buff[..end as usize]);
+
+            let owner = GetWindow(handle, GW_OWNER)
+                .map(|h| h.0 as usize)
+                .unwrap_or(0);
+
+            let mut cloaked = 0u32;
+            let _ = DwmGetWindowAttribute(
+                handle,
+                DWMWA_CLOAKED,
+                &mut cloaked as *mut _ as *mut _,
+                std::mem::size_of::<u32>() as u32,
+            );
+
+            Self {
+                style,
+                ex_style,
+                rect: r.into(),
+                title,
+                owner,
+                cloaked: cloaked != 0,
+            }
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WindowKind {
+    /// Regular application window, it is tracked and laid out.
+    Managed,
+    /// Tool windows, dialogs, hidden or cloaked windows and everything else we should not touch.
+    Ignored,
+}
+
+/// User supplied override. Returning `None` defers to the next rule and finally to the default
+/// classification.
+pub type WindowRule = fn(&WindowDescriptor) -> Option<WindowKind>;
+
+/// Decides whether window described by `desc` should be managed. `rules` are consulted in order
+/// before default rules. Windows failing `can_be_managed` are always ignored.
+pub fn classify_window(desc: &WindowDescriptor, rules: &[WindowRule]) -> WindowKind {
+    if !can_be_managed(desc) {
+        return WindowKind::Ignored;
+    }
+
+    for rule in rules {
+        if let Some(kind) = rule(desc) {
+            return kind;
+        }
+    }
+
+    default_window_kind(desc)
+}
+
+/// Structural checks no rule can override, windows without a sane rect and child windows are never
+/// managed. Hooks dll filters with this alone, user rules are only known to the main process.
+pub fn can_be_managed(desc: &WindowDescriptor) -> bool {
+    has_sane_rect(desc.rect) && !desc.style.contains(WS_CHILD)
+}
+
+fn default_window_kind(desc: &WindowDescriptor) -> WindowKind {
+    let style = desc.style;
+    let ex_style = desc.ex_style;
+
+    if !style.contains(WS_VISIBLE) || desc.cloaked {
+        return WindowKind::Ignored;
+    }
+
+    // Window explicitly asked for a taskbar button, no reason to second guess it.
+    if ex_style.contains(WS_EX_APPWINDOW) {
+        return WindowKind::Managed;
+    }
+
+    if ex_style.contains(WS_EX_TOOLWINDOW) || ex_style.contains(WS_EX_NOACTIVATE) {
+        return WindowKind::Ignored;
+    }
+
+    // Owned windows are dialogs, property sheets and such.
+    if desc.owner != 0 {
+        return WindowKind::Ignored;
+    }
+
+    let has_caption = style.contains(WS_CAPTION);
+
+    // Borderless popups are menus, tooltips and splash screens. Frameless apps (electron etc.)
+    // still keep a sizing border.
+    if style.contains(WS_POPUP) && !has_caption && !style.contains(WS_THICKFRAME) {
+        return WindowKind::Ignored;
+    }
+
+    // Plenty of apps have no title, but those that also lack caption are helper windows.
+    if desc.title.is_empty() && !has_caption {
+        return WindowKind::Ignored;
+    }
+
+    WindowKind::Managed
+}
+
+fn has_sane_rect(rect: Rect) -> bool {
+    let values = [rect.x, rect.y, rect.width, rect.height];
+    if values.iter().any(|v| *v == i32::MAX || *v == i32::MIN) {
+        return false;
+    }
+
+    rect.width != 0 && rect.height != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app_window() -> WindowDescriptor {
+        WindowDescriptor {
+            style: WS_VISIBLE | WS_OVERLAPPEDWINDOW,
+            rect: Rect {
+                x: 100,
+                y: 100,
+                width: 800,
+                height: 600,
+            },
+            title: "Untitled - Notepad".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn regular_window_is_managed() {
+        assert_eq!(classify_window(&app_window(), &[]), WindowKind::Managed);
+    }
+
+    #[test]
+    fn tool_window_is_ignored() {
+        let desc = WindowDescriptor {
+            ex_style: WS_EX_TOOLWINDOW,
+            ..app_window()
+        };
+        assert_eq!(classify_window(&desc, &[]), WindowKind::Ignored);
+    }
+
+    #[test]
+    fn app_window_style_wins_over_tool_window() {
+        let desc = WindowDescriptor {
+            ex_style: WS_EX_TOOLWINDOW | WS_EX_APPWINDOW,
+            ..app_window()
+        };
+        assert_eq!(classify_window(&desc, &[]), WindowKind::Managed);
+    }
+
+    #[test]
+    fn owned_window_is_ignored() {
+        let desc = WindowDescriptor {
+            owner: 0x1234,
+            ..app_window()
+        };
+        assert_eq!(classify_window(&desc, &[]), WindowKind::Ignored);
+    }
+
+    #[test]
+    fn borderless_popup_is_ignored() {
+        let desc = WindowDescriptor {
+            style: WS_VISIBLE | WS_POPUP,
+            ..app_window()
+        };
+        assert_eq!(classify_window(&desc, &[]), WindowKind::Ignored);
+    }
+
+    #[test]
+    fn frameless_popup_with_sizing_border_is_managed() {
+        let desc = WindowDescriptor {
+            style: WS_VISIBLE | WS_POPUP | WS_THICKFRAME,
+            ..app_window()
+        };
+        assert_eq!(classify_window(&desc, &[]), WindowKind::Managed);
+    }
+
+    #[test]
+    fn hidden_and_cloaked_windows_are_ignored() {
+        let hidden = WindowDescriptor {
+            style: WS_OVERLAPPEDWINDOW,
+            ..app_window()
+        };
+        let cloaked = WindowDescriptor {
+            cloaked: true,
+            ..app_window()
+        };
+        assert_eq!(classify_window(&hidden, &[]), WindowKind::Ignored);
+        assert_eq!(classify_window(&cloaked, &[]), WindowKind::Ignored);
+    }
+
+    #[test]
+    fn untitled_window_without_caption_is_ignored() {
+        let desc = WindowDescriptor {
+            style: WS_VISIBLE | WS_THICKFRAME,
+            title: String::new(),
+            ..app_window()
+        };
+        assert_eq!(classify_window(&desc, &[]), WindowKind::Ignored);
+    }
+
+    #[test]
+    fn child_window_is_ignored() {
+        let desc = WindowDescriptor {
+            style: WS_VISIBLE | WS_CHILD | WS_CAPTION,
+            ..app_window()
+        };
+        assert_eq!(classify_window(&desc, &[]), WindowKind::Ignored);
+    }
+
+    #[test]
+    fn zero_size_and_off_screen_rects_are_not_sane() {
+        let rect = app_window().rect;
+        assert!(has_sane_rect(rect));
+        assert!(!has_sane_rect(Rect { width: 0, ..rect }));
+        assert!(!has_sane_rect(Rect { height: 0, ..rect }));
+        assert!(!has_sane_rect(Rect {
+            x: i32::MIN,
+            ..rect
+        }));
+        assert!(!has_sane_rect(Rect {
+            y: i32::MAX,
+            ..rect
+        }));
+    }
+
+    #[test]
+    fn window_without_sane_rect_is_ignored() {
+        let desc = WindowDescriptor {
+            rect: Rect::default(),
+            ..app_window()
+        };
+        assert_eq!(classify_window(&desc, &[]), WindowKind::Ignored);
+    }
+
+    #[test]
+    fn rules_take_precedence_over_defaults() {
+        fn manage_tools(desc: &WindowDescriptor) -> Option<WindowKind> {
+            desc.ex_style
+                .contains(WS_EX_TOOLWINDOW)
+                .then_some(WindowKind::Managed)
+        }
+        fn ignore_notepad(desc: &WindowDescriptor) -> Option<WindowKind> {
+            desc.title
+                .ends_with("Notepad")
+                .then_some(WindowKind::Ignored)
+        }
+
+        let tool = WindowDescriptor {
+            ex_style: WS_EX_TOOLWINDOW,
+            ..app_window()
+        };
+        assert_eq!(classify_window(&tool, &[manage_tools]), WindowKind::Managed);
+        assert_eq!(
+            classify_window(&app_window(), &[manage_tools, ignore_notepad]),
+            WindowKind::Ignored
+        );
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        fn manage(_: &WindowDescriptor) -> Option<WindowKind> {
+            Some(WindowKind::Managed)
+        }
+        fn ignore(_: &WindowDescriptor) -> Option<WindowKind> {
+            Some(WindowKind::Ignored)
+        }
+
+        assert_eq!(
+            classify_window(&app_window(), &[ignore, manage]),
+            WindowKind::Ignored
+        );
+    }
+
+    #[test]
+    fn windows_ignored_by_default_can_be_managed_by_rules() {
+        let tool = WindowDescriptor {
+            ex_style: WS_EX_TOOLWINDOW,
+            ..app_window()
+        };
+        let owned = WindowDescriptor {
+            owner: 0x1234,
+            ..app_window()
+        };
+        assert!(can_be_managed(&tool));
+        assert!(can_be_managed(&owned));
+        assert!(!can_be_managed(&WindowDescriptor {
+            style: WS_VISIBLE | WS_CHILD,
+            ..app_window()
+        }));
+        assert!(!can_be_managed(&WindowDescriptor {
+            rect: Rect::default(),
+            ..app_window()
+        }));
+    }
+
+    #[test]
+    fn rules_cannot_manage_child_windows() {
+        fn manage(_: &WindowDescriptor) -> Option<WindowKind> {
+            Some(WindowKind::Managed)
+        }
+
+        let desc = WindowDescriptor {
+            style: WS_VISIBLE | WS_CHILD,
+            ..app_window()
+        };
+        assert_eq!(classify_window(&desc, &[manage]), WindowKind::Ignored);
+    }
+}
@@ -11,7 +11,7 @@ use windows::Win32::UI::WindowsAndMessaging::*;
 use tracing_appender::rolling::{RollingFileAppender, Rotation};
 use tracing_subscriber::fmt::Subscriber;
 
-use winwin_common::{ClientEvent, Rect};
+use winwin_common::{can_be_managed, ClientEvent, WindowDescriptor};
 
 const PIPE_NAME: PCSTR = s!("\\\\.\\pipe\\winwin_pipe");
 const BUFFER_SIZE: usize = 512;
@@ -75,7 +75,7 @@ pub unsafe extern "system" fn shell_proc(code: i32, wparam: WPARAM, lparam: LPAR
     if code == HSHELL_WINDOWCREATED as _ {
         let hwnd = HWND(wparam.0 as _);
 
-        if is_real_window(hwnd) {
+        if is_manageable_window(hwnd) {
             let hmonitor = MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST);
             let event = ClientEvent::WindowOpen(hwnd.0 as _, hmonitor.0 as _);
             if let Err(e) = send_event(event) {
@@ -98,7 +98,7 @@ pub unsafe extern "system" fn shell_proc(code: i32, wparam: WPARAM, lparam: LPAR
     //     let hwnd = HWND(wparam.0 as _);
     //     let hmonitor = MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST);
     //
-    //     if is_real_window(hwnd) {
+    //     if is_manageable_window(hwnd) {
     //         let event = ClientEvent::WindowMonitorChanged(hwnd.0 as _, hmonitor.0 as _);
     //         if let Err(e) = send_event(event) {
     //             tracing::warn!(?e);
@@ -109,7 +109,7 @@ pub unsafe extern "system" fn shell_proc(code: i32, wparam: WPARAM, lparam: LPAR
     if code == HSHELL_WINDOWACTIVATED as _ {
         let hwnd = HWND(wparam.0 as _);
 
-        if is_real_window(hwnd) {
+        if is_manageable_window(hwnd) {
             let event = ClientEvent::WindowFocusHanged(hwnd.0 as _);
             if let Err(e) = send_event(event) {
                 tracing::warn!(?e);
@@ -117,11 +117,48 @@ pub unsafe extern "system" fn shell_proc(code: i32, wparam: WPARAM, lparam: LPAR
         }
     }
 
+    // Sent on title change, but also when window is flashing, `lparam` tells those apart.
+    if code == HSHELL_REDRAW as _ && lparam.0 == 0 {
+        let hwnd = HWND(wparam.0 as _);
+
+        if is_manageable_window(hwnd) {
+            let event = ClientEvent::WindowTitleChanged(hwnd.0 as _);
+            if let Err(e) = send_event(event) {
+                tracing::warn!(?e);
+            }
+        }
+    }
+
     return CallNextHookEx(None, code, wparam, lparam);
 }
 
 #[no_mangle]
 pub unsafe extern "system" fn cbt_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    // This is called before window changes its state, `IsIconic` still reports the old one.
+    if code == HCBT_MINMAX as _ {
+        let hwnd = HWND(wparam.0 as _);
+        let show_cmd = SHOW_WINDOW_CMD((lparam.0 & 0xFFFF) as _);
+        let minimizing = show_cmd == SW_MINIMIZE
+            || show_cmd == SW_SHOWMINIMIZED
+            || show_cmd == SW_SHOWMINNOACTIVE
+            || show_cmd == SW_FORCEMINIMIZE;
+        let is_minimized = IsIconic(hwnd).as_bool();
+
+        let event = if minimizing && !is_minimized {
+            Some(ClientEvent::WindowMinimized(hwnd.0 as _))
+        } else if !minimizing && is_minimized {
+            Some(ClientEvent::WindowRestored(hwnd.0 as _))
+        } else {
+            None
+        };
+
+        if let Some(event) = event {
+            if let Err(e) = send_event(event) {
+                tracing::warn!(?e);
+            }
+        }
+    }
+
     return CallNextHookEx(None, code, wparam, lparam);
 }
 
@@ -174,24 +211,7 @@ unsafe fn send_event(event: ClientEvent) -> Result<(), HookError> {
     Ok(())
 }
 
-fn is_real_window(handle: HWND) -> bool {
-    let mut r = RECT::default();
-    let _ = unsafe { GetWindowRect(handle, &mut r as *mut _) };
-
-    let this_rect = Rect::from(r);
-    if this_rect.x == i32::MAX
-        || this_rect.y == i32::MAX
-        || this_rect.width == i32::MAX
-        || this_rect.height == i32::MAX
-        || this_rect.x == i32::MIN
-        || this_rect.y == i32::MIN
-        || this_rect.width == i32::MIN
-        || this_rect.height == i32::MIN
-        || this_rect.width == 0
-        || this_rect.height == 0
-    {
-        return false;
-    }
-
-    return true;
+// Only rejects windows no rule can manage, main process classifies the rest with user rules.
+fn is_manageable_window(handle: HWND) -> bool {
+    can_be_managed(&WindowDescriptor::query(handle))
 }
@@ -0,0 +1,551 @@
+use allocator_api2::alloc::Allocator;
+
+use crate::{
+    get_focused_window, Context, Input, Key, KeyCombo, KeyEventKind, ModifierMatching, Tap, Window,
+    DEFAULT_KEY_MODE,
+};
+
+type Action<'a, A> = Box<dyn FnMut(&Context<A>) + 'a>;
+
+pub struct Binding<'a, A: Allocator> {
+    pub mode: &'static str,
+    pub keys: Vec<Key>,
+    /// Shown in help listings and conflict reports.
+    pub description: &'static str,
+    pub trigger: Trigger,
+    /// Whether action runs again on auto-repeat while keys are held.
+    pub repeat: bool,
+    pub scope: Scope<'a>,
+    action: Action<'a, A>,
+}
+
+impl<A: Allocator> Binding<'_, A> {
+    // Hook reports sided keys, generic modifiers match either side like with presses.
+    fn is_tapped(&self, trigger: Trigger, input: &Input<A>) -> bool {
+        self.trigger == trigger
+            && input.in_mode(self.mode)
+            && KeyCombo::from_keys(&self.keys)
+                .is_some_and(|combo| combo.matches(&[input.key()], input.matching))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    /// Keys are pressed.
+    Press,
+    /// Key is tapped alone, see `set_tap_options`.
+    Tap,
+    DoubleTap,
+}
+
+/// Windows a binding is active in, checked against focused window. Keys of inactive bindings are
+/// not intercepted and reach the focused app.
+#[derive(Default)]
+pub enum Scope<'a> {
+    #[default]
+    Global,
+    /// Active only while focused window matches, e.g.
+    /// `|w| get_process_name(&ctx, w) == "code.exe"`.
+    Only(Box<dyn Fn(Window) -> bool + 'a>),
+    /// Active unless focused window matches.
+    Except(Box<dyn Fn(Window) -> bool + 'a>),
+}
+
+impl Scope<'_> {
+    pub fn is_active(&self, window: Window) -> bool {
+        match self {
+            Self::Global => true,
+            Self::Only(matches) => matches(window),
+            Self::Except(matches) => !matches(window),
+        }
+    }
+}
+
+/// Two bindings in the same mode that fire on the same keys, only the first one is ever
+/// dispatched then.
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    pub mode: &'static str,
+    pub keys: Vec<Key>,
+    pub shadowed_by: &'static str,
+    pub shadowed: &'static str,
+}
+
+/// Table of key bindings. Unlike chains of `Input::all_pressed` calls, each key event is matched
+/// against the table once and keypress is intercepted only if some binding matched.
+pub struct Bindings<'a, A: Allocator> {
+    bindings: Vec<Binding<'a, A>>,
+}
+
+impl<'a, A> Bindings<'a, A>
+where
+    A: Allocator + Copy,
+{
+    pub fn new() -> Self {
+        Self {
+            bindings: Vec::new(),
+        }
+    }
+
+    /// Binds `keys` in the default mode. `keys` must be exactly the keys held, in any order.
+    pub fn bind(
+        &mut self,
+        keys: &[Key],
+        description: &'static str,
+        action: impl FnMut(&Context<A>) + 'a,
+    ) -> &mut Self {
+        self.bind_in(DEFAULT_KEY_MODE, keys, description, action)
+    }
+
+    /// Binds `keys` in `mode`, see `set_key_mode`.
+    pub fn bind_in(
+        &mut self,
+        mode: &'static str,
+        keys: &[Key],
+        description: &'static str,
+        action: impl FnMut(&Context<A>) + 'a,
+    ) -> &mut Self {
+        self.push(mode, Trigger::Press, keys, description, action)
+    }
+
+    /// Binds quick tap of `key` alone in the default mode, e.g. tap of `Key::Win` that is
+    /// otherwise used as a modifier. Start menu does not open when Win tap is bound.
+    pub fn bind_tap(
+        &mut self,
+        key: Key,
+        description: &'static str,
+        action: impl FnMut(&Context<A>) + 'a,
+    ) -> &mut Self {
+        self.push(DEFAULT_KEY_MODE, Trigger::Tap, &[key], description, action)
+    }
+
+    /// Binds two quick taps of `key` in the default mode. Tap binding of the same key fires on
+    /// the first tap.
+    pub fn bind_double_tap(
+        &mut self,
+        key: Key,
+        description: &'static str,
+        action: impl FnMut(&Context<A>) + 'a,
+    ) -> &mut Self {
+        self.push(
+            DEFAULT_KEY_MODE,
+            Trigger::DoubleTap,
+            &[key],
+            description,
+            action,
+        )
+    }
+
+    fn push(
+        &mut self,
+        mode: &'static str,
+        trigger: Trigger,
+        keys: &[Key],
+        description: &'static str,
+        action: impl FnMut(&Context<A>) + 'a,
+    ) -> &mut Self {
+        self.bindings.push(Binding {
+            mode,
+            keys: keys.to_vec(),
+            description,
+            trigger,
+            repeat: false,
+            scope: Scope::Global,
+            action: Box::new(action),
+        });
+        self
+    }
+
+    /// Lets the most recently added binding fire on auto-repeat too, e.g. for resizing while keys
+    /// are held.
+    pub fn repeat(&mut self) -> &mut Self {
+        if let Some(binding) = self.bindings.last_mut() {
+            binding.repeat = true;
+        }
+        self
+    }
+
+    /// Makes the most recently added binding active only while focused window matches
+    /// `predicate`.
+    pub fn only_in(&mut self, predicate: impl Fn(Window) -> bool + 'a) -> &mut Self {
+        if let Some(binding) = self.bindings.last_mut() {
+            binding.scope = Scope::Only(Box::new(predicate));
+        }
+        self
+    }
+
+    /// Makes the most recently added binding inactive while focused window matches `predicate`,
+    /// e.g. to let an app keep its own shortcut.
+    pub fn except_in(&mut self, predicate: impl Fn(Window) -> bool + 'a) -> &mut Self {
+        if let Some(binding) = self.bindings.last_mut() {
+            binding.scope = Scope::Except(Box::new(predicate));
+        }
+        self
+    }
+
+    /// Runs the first binding matching `input` and intercepts the keypress. Returns false and
+    /// lets keypress through if nothing matched. Bindings fire on key down only, auto-repeats of
+    /// bound keys are intercepted but run the action only if binding opted in with `repeat`.
+    /// Key releases have to be dispatched too, tap bindings fire on them.
+    pub fn dispatch(&mut self, ctx: &Context<A>, input: &Input<A>) -> bool {
+        let focused = get_focused_window(ctx);
+        if input.kind() == KeyEventKind::Up {
+            return self.dispatch_tap(ctx, input, focused);
+        }
+
+        // Scope is checked last, predicates can be costly.
+        let binding = self.bindings.iter_mut().find(|b| {
+            b.trigger == Trigger::Press
+                && input.in_mode(b.mode)
+                && input.completes_no_intercept(&b.keys)
+                && b.scope.is_active(focused)
+        });
+
+        match binding {
+            Some(binding) => {
+                input.intercept();
+                if input.kind() == KeyEventKind::Down || binding.repeat {
+                    (binding.action)(ctx);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn dispatch_tap(&mut self, ctx: &Context<A>, input: &Input<A>, focused: Window) -> bool {
+        let trigger = match input.tap() {
+            Some(Tap::Single) => Trigger::Tap,
+            Some(Tap::Double) => Trigger::DoubleTap,
+            None => return false,
+        };
+
+        let binding = self
+            .bindings
+            .iter_mut()
+            .find(|b| b.is_tapped(trigger, input) && b.scope.is_active(focused));
+
+        match binding {
+            Some(binding) => {
+                input.intercept_masked();
+                (binding.action)(ctx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Bindings that are fully or partially shadowed by earlier ones, e.g. `LShift+J` after
+    /// `Shift+J`. Depends on `matching`, see `set_modifier_matching`. Scoped bindings never shadow
+    /// others, they are meant to override later ones in some windows only.
+    pub fn conflicts(&self, matching: ModifierMatching) -> Vec<Conflict> {
+        let combos: Vec<Option<KeyCombo>> = self
+            .bindings
+            .iter()
+            .map(|b| KeyCombo::from_keys(&b.keys))
+            .collect();
+
+        let mut conflicts = Vec::new();
+        for (i, binding) in self.bindings.iter().enumerate() {
+            let Some(combo) = &combos[i] else {
+                continue;
+            };
+
+            let first = self.bindings[..i].iter().zip(&combos).find(|(b, c)| {
+                b.mode == binding.mode
+                    && b.trigger == binding.trigger
+                    && matches!(b.scope, Scope::Global)
+                    && c.as_ref().is_some_and(|c| c.overlaps(combo, matching))
+            });
+
+            if let Some((first, _)) = first {
+                conflicts.push(Conflict {
+                    mode: binding.mode,
+                    keys: binding.keys.clone(),
+                    shadowed_by: first.description,
+                    shadowed: binding.description,
+                });
+            }
+        }
+        conflicts
+    }
+
+    /// All bindings in the order they were added, e.g. for help overlay.
+    pub fn iter(&self) -> impl Iterator<Item = &Binding<'a, A>> {
+        self.bindings.iter()
+    }
+}
+
+impl<A> Default for Bindings<'_, A>
+where
+    A: Allocator + Copy,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::KeyboardReply;
+    use crate::{KeyboardOp, Monitor};
+    use allocator_api2::alloc::Global;
+    use std::cell::Cell;
+    use std::sync::mpsc::{sync_channel, Receiver};
+
+    fn context() -> Context {
+        let ctx = Context::new();
+        ctx.cache.add_window_queue(Monitor::default());
+        ctx
+    }
+
+    fn input(
+        pressed: &[Key],
+        key: Key,
+        kind: KeyEventKind,
+    ) -> (Input<Global>, Receiver<KeyboardReply>) {
+        let (tx, rx) = sync_channel(2);
+        let mut keys = allocator_api2::vec::Vec::new_in(Global);
+        keys.extend_from_slice(pressed);
+        let input = Input {
+            keys,
+            key,
+            kind,
+            tap: None,
+            mode: DEFAULT_KEY_MODE,
+            matching: ModifierMatching::SideAgnostic,
+            seq: 1,
+            intercept_tx: tx,
+        };
+        (input, rx)
+    }
+
+    // Dispatches keys held after `key` went down and returns what hook was told to do with it.
+    fn press(bindings: &mut Bindings<Global>, ctx: &Context, pressed: &[Key]) -> KeyboardOp {
+        let key = *pressed.last().unwrap();
+        let (input, rx) = input(pressed, key, KeyEventKind::Down);
+        bindings.dispatch(ctx, &input);
+        drop(input);
+        rx.try_recv().unwrap().op
+    }
+
+    #[test]
+    fn dispatch_runs_first_matching_binding() {
+        let ctx = context();
+        let first = Cell::new(0);
+        let second = Cell::new(0);
+        let mut bindings = Bindings::new();
+        bindings
+            .bind(&[Key::AltLeft, Key::J], "first", |_| {
+                first.set(first.get() + 1)
+            })
+            .bind(&[Key::Menu, Key::J], "second", |_| {
+                second.set(second.get() + 1)
+            });
+
+        let op = press(&mut bindings, &ctx, &[Key::AltLeft, Key::J]);
+        assert_eq!(op, KeyboardOp::InterceptKeypress);
+        assert_eq!((first.get(), second.get()), (1, 0));
+    }
+
+    #[test]
+    fn dispatch_lets_unbound_keys_through() {
+        let ctx = context();
+        let calls = Cell::new(0);
+        let mut bindings = Bindings::new();
+        bindings.bind(&[Key::AltLeft, Key::J], "j", |_| calls.set(calls.get() + 1));
+
+        let op = press(&mut bindings, &ctx, &[Key::AltLeft, Key::K]);
+        assert_eq!(op, KeyboardOp::DoNothing);
+        let op = press(&mut bindings, &ctx, &[Key::J]);
+        assert_eq!(op, KeyboardOp::DoNothing);
+        let op = press(&mut bindings, &ctx, &[Key::J, Key::AltLeft]);
+        assert_eq!(op, KeyboardOp::DoNothing);
+        assert_eq!(calls.get(), 0);
+    }
+
+    #[test]
+    fn dispatch_respects_modes() {
+        let ctx = context();
+        let calls = Cell::new(0);
+        let mut bindings = Bindings::new();
+        bindings.bind_in("resize", &[Key::H], "grow", |_| calls.set(calls.get() + 1));
+
+        let op = press(&mut bindings, &ctx, &[Key::H]);
+        assert_eq!(op, KeyboardOp::DoNothing);
+
+        let (mut input, rx) = input(&[Key::H], Key::H, KeyEventKind::Down);
+        input.mode = "resize";
+        assert!(bindings.dispatch(&ctx, &input));
+        drop(input);
+        assert_eq!(rx.try_recv().unwrap().op, KeyboardOp::InterceptKeypress);
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn dispatch_runs_action_on_repeat_only_if_opted_in() {
+        let ctx = context();
+        let once = Cell::new(0);
+        let repeated = Cell::new(0);
+        let mut bindings = Bindings::new();
+        bindings
+            .bind(&[Key::J], "once", |_| once.set(once.get() + 1))
+            .bind(&[Key::K], "repeated", |_| repeated.set(repeated.get() + 1))
+            .repeat();
+
+        for key in [Key::J, Key::K] {
+            let (input, rx) = input(&[key], key, KeyEventKind::Repeat);
+            assert!(bindings.dispatch(&ctx, &input));
+            drop(input);
+            assert_eq!(rx.try_recv().unwrap().op, KeyboardOp::InterceptKeypress);
+        }
+        assert_eq!((once.get(), repeated.get()), (0, 1));
+    }
+
+    #[test]
+    fn dispatch_ignores_releases_of_press_bindings() {
+        let ctx = context();
+        let calls = Cell::new(0);
+        let mut bindings = Bindings::new();
+        bindings.bind(&[Key::J], "j", |_| calls.set(calls.get() + 1));
+
+        let (input, _rx) = input(&[], Key::J, KeyEventKind::Up);
+        assert!(!bindings.dispatch(&ctx, &input));
+        assert_eq!(calls.get(), 0);
+    }
+
+    fn noop(_: &Context) {}
+
+    #[test]
+    fn taps_match_either_side_of_generic_modifiers() {
+        let mut bindings = Bindings::new();
+        bindings
+            .bind_tap(Key::Shift, "shift", noop)
+            .bind_tap(Key::AltLeft, "left alt", noop)
+            .bind_double_tap(Key::Win, "win", noop);
+        let [shift, left_alt, win] = [0, 1, 2].map(|i| &bindings.bindings[i]);
+
+        let (shift_up, _rx) = input(&[], Key::ShiftRight, KeyEventKind::Up);
+        assert!(shift.is_tapped(Trigger::Tap, &shift_up));
+        assert!(!shift.is_tapped(Trigger::DoubleTap, &shift_up));
+        assert!(!left_alt.is_tapped(Trigger::Tap, &shift_up));
+
+        let (mut alt_up, _rx) = input(&[], Key::AltRight, KeyEventKind::Up);
+        assert!(left_alt.is_tapped(Trigger::Tap, &alt_up));
+        alt_up.matching = ModifierMatching::Strict;
+        assert!(!left_alt.is_tapped(Trigger::Tap, &alt_up));
+
+        let (win_up, _rx) = input(&[], Key::Win, KeyEventKind::Up);
+        assert!(win.is_tapped(Trigger::DoubleTap, &win_up));
+        assert!(!win.is_tapped(Trigger::Tap, &win_up));
+    }
+
+    #[test]
+    fn scope_resolution() {
+        let editor = Window::from(0x10);
+        let other = Window::from(0x20);
+        let only = Scope::Only(Box::new(move |w| w == editor));
+        let except = Scope::Except(Box::new(move |w| w == editor));
+        assert!(Scope::Global.is_active(editor));
+        assert!(Scope::Global.is_active(other));
+        assert!(only.is_active(editor));
+        assert!(!only.is_active(other));
+        assert!(!except.is_active(editor));
+        assert!(except.is_active(other));
+    }
+
+    #[test]
+    fn dispatch_skips_bindings_scoped_out_of_focused_window() {
+        let ctx = context();
+        let focused = get_focused_window(&ctx);
+        let ran = Cell::new("");
+        let mut bindings = Bindings::new();
+        bindings
+            .bind(&[Key::AltLeft, Key::H], "elsewhere", |_| {
+                ran.set("elsewhere")
+            })
+            .only_in(move |w| w != focused)
+            .bind(&[Key::AltLeft, Key::H], "not here", |_| ran.set("not here"))
+            .except_in(move |w| w == focused);
+        assert_eq!(
+            press(&mut bindings, &ctx, &[Key::AltLeft, Key::H]),
+            KeyboardOp::DoNothing
+        );
+        assert_eq!(ran.get(), "");
+
+        bindings
+            .bind(&[Key::AltLeft, Key::H], "here", |_| ran.set("here"))
+            .only_in(move |w| w == focused);
+        assert_eq!(
+            press(&mut bindings, &ctx, &[Key::AltLeft, Key::H]),
+            KeyboardOp::InterceptKeypress
+        );
+        assert_eq!(ran.get(), "here");
+    }
+
+    #[test]
+    fn scoped_bindings_do_not_shadow_later_ones() {
+        let mut bindings = Bindings::new();
+        bindings
+            .bind(&[Key::AltLeft, Key::H], "in editor", noop)
+            .only_in(|_| true)
+            .bind(&[Key::AltLeft, Key::H], "global", noop)
+            .bind(&[Key::AltLeft, Key::H], "outside editor", noop)
+            .except_in(|_| true);
+        let conflicts = bindings.conflicts(ModifierMatching::SideAgnostic);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].shadowed, "outside editor");
+        assert_eq!(conflicts[0].shadowed_by, "global");
+    }
+
+    #[test]
+    fn no_conflicts() {
+        let mut bindings = Bindings::new();
+        bindings
+            .bind(&[Key::AltLeft, Key::J], "j", noop)
+            .bind(&[Key::AltLeft, Key::K], "k", noop)
+            .bind(&[Key::AltLeft, Key::ShiftLeft, Key::J], "shift j", noop)
+            .bind_in("resize", &[Key::AltLeft, Key::J], "other mode", noop)
+            .bind_tap(Key::J, "tap", noop);
+        assert!(bindings
+            .conflicts(ModifierMatching::SideAgnostic)
+            .is_empty());
+    }
+
+    #[test]
+    fn same_keys_conflict() {
+        let mut bindings = Bindings::new();
+        bindings.bind(&[Key::AltLeft, Key::J], "first", noop).bind(
+            &[Key::J, Key::AltLeft],
+            "second",
+            noop,
+        );
+        let conflicts = bindings.conflicts(ModifierMatching::SideAgnostic);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].shadowed, "second");
+        assert_eq!(conflicts[0].shadowed_by, "first");
+        assert_eq!(conflicts[0].keys, [Key::J, Key::AltLeft]);
+        assert_eq!(conflicts[0].mode, DEFAULT_KEY_MODE);
+    }
+
+    #[test]
+    fn conflicts_depend_on_modifier_matching() {
+        let mut bindings = Bindings::new();
+        bindings.bind(&[Key::ShiftLeft, Key::J], "left", noop).bind(
+            &[Key::ShiftRight, Key::J],
+            "right",
+            noop,
+        );
+        assert_eq!(bindings.conflicts(ModifierMatching::SideAgnostic).len(), 1);
+        assert!(bindings.conflicts(ModifierMatching::Strict).is_empty());
+
+        let mut bindings = Bindings::new();
+        bindings.bind(&[Key::Shift, Key::J], "generic", noop).bind(
+            &[Key::ShiftLeft, Key::J],
+            "left",
+            noop,
+        );
+        assert_eq!(bindings.conflicts(ModifierMatching::Strict).len(), 1);
+    }
+}
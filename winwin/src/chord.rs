@@ -0,0 +1,552 @@
+use allocator_api2::alloc::Allocator;
+use std::time::{Duration, Instant};
+use winwin_common::{KBDelta, Key, KeyState};
+
+use crate::Context;
+
+/// Identifies sequence registered with `add_chord`, reported back by `Event::Chord`.
+pub type ChordId = usize;
+
+/// What happens to keystrokes of a sequence that timed out or was followed by unbound key.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AbandonPolicy {
+    /// Keystrokes are dropped, apps never see them.
+    #[default]
+    Swallow,
+    /// Keystrokes are sent again to focused app in the order they were pressed.
+    Replay,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChordOutcome {
+    /// Keystroke is not part of any sequence.
+    PassThrough,
+    /// Keystroke was consumed by a pending sequence.
+    Swallow,
+    /// Keystroke completed a sequence. It is consumed too.
+    Matched(ChordId),
+}
+
+const DEFAULT_CHORD_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Prefix key state machine. Each step of a sequence is the exact set of keys held down when its
+/// last key goes down, e.g. `[[AltLeft, Space], [W], [N3]]`. It holds no handles and never talks to
+/// the system so it can be driven by any stream of `KBDelta`s.
+///
+/// Sequences that are prefixes of other sequences always win, the longer ones can never match.
+/// `add` warns about such collisions.
+pub struct ChordMachine {
+    sequences: Vec<Vec<Vec<Key>>>,
+    timeout: Duration,
+    policy: AbandonPolicy,
+    // Number of steps already matched, 0 when nothing is pending.
+    progress: usize,
+    // Sequences matching all steps so far.
+    candidates: Vec<ChordId>,
+    deadline: Option<Instant>,
+    // Keystrokes of the pending sequence in order, replayed if sequence is abandoned.
+    buffer: Vec<KBDelta>,
+    // Keys whose key down was consumed and are still held, their repeats and key ups are consumed
+    // too so apps never see half of a keystroke.
+    swallowed: Vec<u8>,
+    replay: Vec<KBDelta>,
+}
+
+impl Default for ChordMachine {
+    fn default() -> Self {
+        Self {
+            sequences: Vec::new(),
+            timeout: DEFAULT_CHORD_TIMEOUT,
+            policy: AbandonPolicy::default(),
+            progress: 0,
+            candidates: Vec::new(),
+            deadline: None,
+            buffer: Vec::new(),
+            swallowed: Vec::new(),
+            replay: Vec::new(),
+        }
+    }
+}
+
+impl ChordMachine {
+    pub fn add(&mut self, steps: &[&[Key]]) -> ChordId {
+        let steps: Vec<Vec<Key>> = steps.iter().map(|step| step.to_vec()).collect();
+        if let Some(other) = self.colliding(&steps) {
+            tracing::warn!(
+                other,
+                id = self.sequences.len(),
+                "one of chords is a prefix of the other, the longer one never matches"
+            );
+        }
+        self.sequences.push(steps);
+        self.sequences.len() - 1
+    }
+
+    // First sequence that is a prefix of `steps` or has `steps` as its prefix.
+    fn colliding(&self, steps: &[Vec<Key>]) -> Option<ChordId> {
+        self.sequences
+            .iter()
+            .position(|sequence| sequence.iter().zip(steps).all(|(a, b)| step_matches(a, b)))
+    }
+
+    /// Sets time allowed between steps and what to do with keystrokes of abandoned sequences.
+    pub fn set_options(&mut self, timeout: Duration, policy: AbandonPolicy) {
+        self.timeout = timeout;
+        self.policy = policy;
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.progress != 0
+    }
+
+    /// Point in time after which pending sequence is abandoned, `tick` has to be called then.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Advances the machine with `delta`. `pressed` are keys held after `delta` was applied.
+    pub fn feed(&mut self, delta: KBDelta, pressed: &[Key], now: Instant) -> ChordOutcome {
+        self.tick(now);
+
+        if let Some(pos) = self.swallowed.iter().position(|k| *k == delta.vk_code) {
+            if matches!(delta.key_state, KeyState::Up) {
+                self.swallowed.swap_remove(pos);
+            }
+            if self.is_pending() {
+                self.buffer.push(delta);
+            }
+            return ChordOutcome::Swallow;
+        }
+
+        if matches!(delta.key_state, KeyState::Up) {
+            return ChordOutcome::PassThrough;
+        }
+
+        let step = self.progress;
+        let matching: Vec<ChordId> = self
+            .candidate_ids()
+            .filter(|id| step_matches(&self.sequences[*id][step], pressed))
+            .collect();
+
+        if !matching.is_empty() {
+            self.consume(delta);
+            self.progress += 1;
+
+            if let Some(id) = matching
+                .iter()
+                .copied()
+                .find(|id| self.sequences[*id].len() == self.progress)
+            {
+                self.reset();
+                return ChordOutcome::Matched(id);
+            }
+
+            self.candidates = matching;
+            self.deadline = Some(now + self.timeout);
+            return ChordOutcome::Swallow;
+        }
+
+        if !self.is_pending() {
+            return ChordOutcome::PassThrough;
+        }
+
+        // Modifier of the next step went down, wait for the rest of it.
+        let is_partial_step = self
+            .candidates
+            .iter()
+            .any(|id| is_partial_step(&self.sequences[*id][step], pressed));
+        self.consume(delta);
+        if !is_partial_step {
+            self.abandon();
+        }
+
+        ChordOutcome::Swallow
+    }
+
+    /// Abandons pending sequence if its deadline has passed.
+    pub fn tick(&mut self, now: Instant) {
+        if self.deadline.is_some_and(|deadline| now >= deadline) {
+            self.abandon();
+        }
+    }
+
+    /// Keystrokes of abandoned sequences that have to be sent to focused app.
+    pub fn take_replay(&mut self) -> Vec<KBDelta> {
+        std::mem::take(&mut self.replay)
+    }
+
+    fn candidate_ids(&self) -> impl Iterator<Item = ChordId> + '_ {
+        let all = if self.is_pending() {
+            None
+        } else {
+            Some(0..self.sequences.len())
+        };
+
+        all.into_iter()
+            .flatten()
+            .chain(self.candidates.iter().copied())
+            .filter(|id| self.sequences[*id].len() > self.progress)
+    }
+
+    fn consume(&mut self, delta: KBDelta) {
+        self.swallowed.push(delta.vk_code);
+        self.buffer.push(delta);
+    }
+
+    fn abandon(&mut self) {
+        match self.policy {
+            AbandonPolicy::Swallow => self.buffer.clear(),
+            AbandonPolicy::Replay => {
+                self.replay.append(&mut self.buffer);
+                // Key downs were replayed, key ups have to reach the app as well.
+                self.swallowed.clear();
+            }
+        }
+        self.reset();
+    }
+
+    fn reset(&mut self) {
+        self.progress = 0;
+        self.candidates.clear();
+        self.deadline = None;
+        self.buffer.clear();
+    }
+}
+
+fn step_matches(step: &[Key], pressed: &[Key]) -> bool {
+    step.len() == pressed.len() && step.iter().all(|k| pressed.contains(k))
+}
+
+// Only modifiers count, the key completing a step has to go down last.
+fn is_partial_step(step: &[Key], pressed: &[Key]) -> bool {
+    pressed.len() < step.len() && pressed.iter().all(|k| k.is_modifier() && step.contains(k))
+}
+
+/// Registers multi stroke binding, e.g. `&[&[Key::AltLeft, Key::Space], &[Key::W], &[Key::N3]]`.
+/// Each step is the exact set of keys held when its last key goes down. Completed sequences are
+/// reported with `Event::Chord`.
+pub fn add_chord<A>(ctx: &Context<A>, steps: &[&[Key]]) -> ChordId
+where
+    A: Allocator + Copy,
+{
+    ctx.cache.add_chord(steps)
+}
+
+/// Sets time allowed between steps of a sequence, one second by default, and what happens to
+/// keystrokes of abandoned sequences.
+pub fn set_chord_options<A>(ctx: &Context<A>, timeout: Duration, policy: AbandonPolicy)
+where
+    A: Allocator + Copy,
+{
+    ctx.cache.set_chord_options(timeout, policy);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::HeldKeys;
+
+    struct Keyboard {
+        machine: ChordMachine,
+        pressed: HeldKeys,
+        now: Instant,
+    }
+
+    impl Keyboard {
+        fn new(sequences: &[&[&[Key]]], policy: AbandonPolicy) -> Self {
+            let mut machine = ChordMachine::default();
+            machine.set_options(Duration::from_millis(500), policy);
+            for steps in sequences {
+                machine.add(steps);
+            }
+            Self {
+                machine,
+                pressed: HeldKeys::default(),
+                now: Instant::now(),
+            }
+        }
+
+        fn down(&mut self, key: Key) -> ChordOutcome {
+            self.pressed.down(key);
+            self.feed(key, KeyState::Down)
+        }
+
+        fn up(&mut self, key: Key) -> ChordOutcome {
+            self.pressed.up(key);
+            self.feed(key, KeyState::Up)
+        }
+
+        fn tap(&mut self, key: Key) -> ChordOutcome {
+            let outcome = self.down(key);
+            self.up(key);
+            outcome
+        }
+
+        fn wait(&mut self, duration: Duration) {
+            self.now += duration;
+            self.machine.tick(self.now);
+        }
+
+        fn feed(&mut self, key: Key, key_state: KeyState) -> ChordOutcome {
+            let delta = KBDelta {
+                vk_code: key.to_vk_code(),
+                key_state,
+                time: 0,
+            };
+            self.machine.feed(delta, self.pressed.as_slice(), self.now)
+        }
+
+        fn replay(&mut self) -> Vec<(Key, KeyState)> {
+            self.machine
+                .take_replay()
+                .iter()
+                .map(|delta| (Key::from_vk_code(delta.vk_code), delta.key_state))
+                .collect()
+        }
+    }
+
+    const LEADER: &[Key] = &[Key::AltLeft, Key::Space];
+
+    #[test]
+    fn detects_prefix_collisions() {
+        let mut machine = ChordMachine::default();
+        machine.add(&[LEADER, &[Key::W], &[Key::N3]]);
+        machine.add(&[LEADER, &[Key::G]]);
+
+        assert_eq!(machine.colliding(&[LEADER.to_vec(), vec![Key::W]]), Some(0));
+        assert_eq!(
+            machine.colliding(&[vec![Key::Space, Key::AltLeft], vec![Key::G], vec![Key::J]]),
+            Some(1)
+        );
+        assert_eq!(machine.colliding(&[LEADER.to_vec(), vec![Key::S]]), None);
+        assert_eq!(
+            machine.colliding(&[vec![Key::AltLeft, Key::J], vec![Key::W]]),
+            None
+        );
+    }
+
+    #[test]
+    fn completes_chord() {
+        let mut kb = Keyboard::new(&[&[LEADER, &[Key::W], &[Key::N3]]], AbandonPolicy::Swallow);
+        assert_eq!(kb.down(Key::AltLeft), ChordOutcome::PassThrough);
+        assert_eq!(kb.tap(Key::Space), ChordOutcome::Swallow);
+        assert_eq!(kb.up(Key::AltLeft), ChordOutcome::PassThrough);
+        assert!(kb.machine.is_pending());
+        assert_eq!(kb.tap(Key::W), ChordOutcome::Swallow);
+        assert_eq!(kb.tap(Key::N3), ChordOutcome::Matched(0));
+        assert!(!kb.machine.is_pending());
+        assert_eq!(kb.machine.deadline(), None);
+        assert!(kb.replay().is_empty());
+    }
+
+    #[test]
+    fn unrelated_keys_pass_through() {
+        let mut kb = Keyboard::new(&[&[LEADER, &[Key::W]]], AbandonPolicy::Swallow);
+        assert_eq!(kb.down(Key::W), ChordOutcome::PassThrough);
+        assert_eq!(kb.up(Key::W), ChordOutcome::PassThrough);
+        assert!(!kb.machine.is_pending());
+    }
+
+    #[test]
+    fn swallows_repeats_and_key_ups_of_consumed_keys() {
+        let mut kb = Keyboard::new(&[&[LEADER, &[Key::W]]], AbandonPolicy::Swallow);
+        kb.down(Key::AltLeft);
+        assert_eq!(kb.down(Key::Space), ChordOutcome::Swallow);
+        assert_eq!(kb.down(Key::Space), ChordOutcome::Swallow);
+        assert_eq!(kb.up(Key::Space), ChordOutcome::Swallow);
+        kb.up(Key::AltLeft);
+        assert_eq!(kb.down(Key::W), ChordOutcome::Matched(0));
+        assert_eq!(kb.up(Key::W), ChordOutcome::Swallow);
+        assert_eq!(kb.up(Key::W), ChordOutcome::PassThrough);
+    }
+
+    #[test]
+    fn times_out_through_tick() {
+        let mut kb = Keyboard::new(&[&[LEADER, &[Key::W]]], AbandonPolicy::Swallow);
+        kb.down(Key::AltLeft);
+        kb.tap(Key::Space);
+        kb.up(Key::AltLeft);
+        let deadline = kb.machine.deadline().unwrap();
+        assert_eq!(deadline, kb.now + Duration::from_millis(500));
+
+        kb.wait(Duration::from_millis(499));
+        assert!(kb.machine.is_pending());
+        kb.wait(Duration::from_millis(1));
+        assert!(!kb.machine.is_pending());
+        assert_eq!(kb.machine.deadline(), None);
+        assert_eq!(kb.tap(Key::W), ChordOutcome::PassThrough);
+    }
+
+    #[test]
+    fn every_step_restarts_the_timeout() {
+        let mut kb = Keyboard::new(
+            &[&[&[Key::F13], &[Key::W], &[Key::N3]]],
+            AbandonPolicy::Swallow,
+        );
+        kb.tap(Key::F13);
+        kb.wait(Duration::from_millis(400));
+        kb.tap(Key::W);
+        kb.wait(Duration::from_millis(400));
+        assert!(kb.machine.is_pending());
+        assert_eq!(kb.tap(Key::N3), ChordOutcome::Matched(0));
+    }
+
+    #[test]
+    fn timeout_with_swallow_policy_drops_keystrokes() {
+        let mut kb = Keyboard::new(&[&[&[Key::F13], &[Key::W]]], AbandonPolicy::Swallow);
+        kb.tap(Key::F13);
+        kb.wait(Duration::from_secs(1));
+        assert!(kb.replay().is_empty());
+    }
+
+    #[test]
+    fn timeout_with_replay_policy_replays_keystrokes() {
+        let mut kb = Keyboard::new(&[&[LEADER, &[Key::W]]], AbandonPolicy::Replay);
+        kb.down(Key::AltLeft);
+        kb.tap(Key::Space);
+        kb.up(Key::AltLeft);
+        kb.wait(Duration::from_secs(1));
+        assert_eq!(
+            kb.replay(),
+            [(Key::Space, KeyState::Down), (Key::Space, KeyState::Up)]
+        );
+        assert!(kb.replay().is_empty());
+    }
+
+    #[test]
+    fn unbound_key_with_swallow_policy_drops_keystrokes() {
+        let mut kb = Keyboard::new(&[&[&[Key::F13], &[Key::W]]], AbandonPolicy::Swallow);
+        kb.tap(Key::F13);
+        assert_eq!(kb.down(Key::Q), ChordOutcome::Swallow);
+        assert!(!kb.machine.is_pending());
+        assert_eq!(kb.up(Key::Q), ChordOutcome::Swallow);
+        assert!(kb.replay().is_empty());
+    }
+
+    #[test]
+    fn unbound_key_with_replay_policy_replays_keystrokes() {
+        let mut kb = Keyboard::new(&[&[&[Key::F13], &[Key::W]]], AbandonPolicy::Replay);
+        kb.tap(Key::F13);
+        assert_eq!(kb.down(Key::Q), ChordOutcome::Swallow);
+        assert_eq!(
+            kb.replay(),
+            [
+                (Key::F13, KeyState::Down),
+                (Key::F13, KeyState::Up),
+                (Key::Q, KeyState::Down),
+            ]
+        );
+        // Key down was replayed so its key up must reach the app.
+        assert_eq!(kb.up(Key::Q), ChordOutcome::PassThrough);
+    }
+
+    #[test]
+    fn replay_keeps_key_ups_of_keys_still_held() {
+        let mut kb = Keyboard::new(&[&[&[Key::F13], &[Key::W]]], AbandonPolicy::Replay);
+        kb.down(Key::F13);
+        kb.wait(Duration::from_secs(1));
+        assert_eq!(kb.replay(), [(Key::F13, KeyState::Down)]);
+        assert_eq!(kb.up(Key::F13), ChordOutcome::PassThrough);
+    }
+
+    #[test]
+    fn prefix_sequence_wins() {
+        let mut kb = Keyboard::new(
+            &[
+                &[&[Key::F13], &[Key::W], &[Key::N3]],
+                &[&[Key::F13], &[Key::W]],
+            ],
+            AbandonPolicy::Swallow,
+        );
+        kb.tap(Key::F13);
+        assert_eq!(kb.tap(Key::W), ChordOutcome::Matched(1));
+        assert!(!kb.machine.is_pending());
+        assert_eq!(kb.tap(Key::N3), ChordOutcome::PassThrough);
+    }
+
+    #[test]
+    fn shared_prefix_is_resolved_by_next_step() {
+        let mut kb = Keyboard::new(
+            &[&[&[Key::F13], &[Key::W]], &[&[Key::F13], &[Key::Q]]],
+            AbandonPolicy::Swallow,
+        );
+        assert_eq!(kb.tap(Key::F13), ChordOutcome::Swallow);
+        assert_eq!(kb.tap(Key::Q), ChordOutcome::Matched(1));
+        assert_eq!(kb.tap(Key::F13), ChordOutcome::Swallow);
+        assert_eq!(kb.tap(Key::W), ChordOutcome::Matched(0));
+    }
+
+    #[test]
+    fn same_sequence_twice_matches_first() {
+        let mut kb = Keyboard::new(
+            &[&[&[Key::F13], &[Key::W]], &[&[Key::F13], &[Key::W]]],
+            AbandonPolicy::Swallow,
+        );
+        kb.tap(Key::F13);
+        assert_eq!(kb.tap(Key::W), ChordOutcome::Matched(0));
+    }
+
+    #[test]
+    fn step_requires_exact_set_of_keys() {
+        let mut kb = Keyboard::new(&[&[&[Key::F13], &[Key::W]]], AbandonPolicy::Swallow);
+        kb.down(Key::ShiftLeft);
+        assert_eq!(kb.tap(Key::F13), ChordOutcome::PassThrough);
+        kb.up(Key::ShiftLeft);
+        assert!(!kb.machine.is_pending());
+    }
+
+    #[test]
+    fn waits_for_rest_of_step_after_its_modifier() {
+        let mut kb = Keyboard::new(
+            &[&[LEADER, &[Key::CtrlLeft, Key::W]]],
+            AbandonPolicy::Swallow,
+        );
+        kb.down(Key::AltLeft);
+        kb.tap(Key::Space);
+        kb.up(Key::AltLeft);
+        assert_eq!(kb.down(Key::CtrlLeft), ChordOutcome::Swallow);
+        assert!(kb.machine.is_pending());
+        assert_eq!(kb.tap(Key::W), ChordOutcome::Matched(0));
+        assert_eq!(kb.up(Key::CtrlLeft), ChordOutcome::Swallow);
+    }
+
+    #[test]
+    fn modifier_released_mid_chord_is_not_needed_by_later_steps() {
+        let mut kb = Keyboard::new(&[&[LEADER, &[Key::W]]], AbandonPolicy::Swallow);
+        kb.down(Key::AltLeft);
+        kb.down(Key::Space);
+        assert_eq!(kb.up(Key::AltLeft), ChordOutcome::PassThrough);
+        assert_eq!(kb.up(Key::Space), ChordOutcome::Swallow);
+        assert_eq!(kb.tap(Key::W), ChordOutcome::Matched(0));
+    }
+
+    #[test]
+    fn modifier_released_mid_chord_abandons_step_requiring_it() {
+        let mut kb = Keyboard::new(&[&[LEADER, &[Key::AltLeft, Key::W]]], AbandonPolicy::Replay);
+        kb.down(Key::AltLeft);
+        kb.tap(Key::Space);
+        kb.up(Key::AltLeft);
+        assert_eq!(kb.down(Key::W), ChordOutcome::Swallow);
+        assert!(!kb.machine.is_pending());
+        assert_eq!(
+            kb.replay(),
+            [
+                (Key::Space, KeyState::Down),
+                (Key::Space, KeyState::Up),
+                (Key::W, KeyState::Down),
+            ]
+        );
+    }
+
+    #[test]
+    fn modifier_held_through_chord_matches_steps_requiring_it() {
+        let mut kb = Keyboard::new(
+            &[&[LEADER, &[Key::AltLeft, Key::W]]],
+            AbandonPolicy::Swallow,
+        );
+        kb.down(Key::AltLeft);
+        kb.tap(Key::Space);
+        assert_eq!(kb.tap(Key::W), ChordOutcome::Matched(0));
+        assert_eq!(kb.up(Key::AltLeft), ChordOutcome::PassThrough);
+    }
+}
@@ -0,0 +1,125 @@
+use allocator_api2::alloc::Allocator;
+use winwin_common::{KeyCombo, MouseButton, Point, Rect};
+
+use crate::{wm, Context, Key, Window};
+
+/// Smallest size window can be shrunk to with a resize drag.
+const MIN_DRAG_SIZE: i32 = 100;
+
+/// What happens to a tiled window dragged with `MouseDrag::modifier`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TiledDrag {
+    /// Window leaves the layout and stays wherever it is dropped.
+    Float,
+    /// Window dropped over another tile swaps places with it, otherwise it snaps back.
+    Swap,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MouseDrag {
+    /// Held alone with left button moves window under cursor, with right button resizes it.
+    /// Generic `Key::Menu` matches both Alt keys.
+    pub modifier: Key,
+    pub tiled: TiledDrag,
+}
+
+/// Enables moving and resizing windows by dragging them anywhere with `MouseDrag::modifier` held.
+/// Disabled by default, while enabled clicks with the modifier held never reach apps.
+pub fn set_mouse_drag<A>(ctx: &Context<A>, mouse_drag: Option<MouseDrag>)
+where
+    A: Allocator + Copy,
+{
+    ctx.cache.set_mouse_drag(mouse_drag);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DragKind {
+    Move,
+    Resize,
+}
+
+/// Drag that is going on, it ends once `button` is released.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Drag {
+    pub window: Window,
+    pub kind: DragKind,
+    pub button: MouseButton,
+    start_point: Point,
+    start_rect: Rect,
+    swap_on_drop: bool,
+}
+
+/// Starts drag if `button` went down over a managed window with drag modifier held.
+pub(crate) fn begin_drag<A>(ctx: &Context<A>, button: MouseButton, point: Point) -> Option<Drag>
+where
+    A: Allocator + Copy,
+{
+    let settings = ctx.cache.mouse_drag()?;
+    let kind = match button {
+        MouseButton::Left => DragKind::Move,
+        MouseButton::Right => DragKind::Resize,
+        _ => return None,
+    };
+
+    let pressed = ctx.cache.pressed_keys();
+    if !KeyCombo::new(&[], settings.modifier).matches(&pressed, ctx.cache.modifier_matching()) {
+        return None;
+    }
+
+    let window = wm::get_window_at(point);
+    if !ctx.cache.contains_window(window) || ctx.cache.is_fullscreen(window) {
+        return None;
+    }
+
+    let start_rect = window.rect();
+    let mut swap_on_drop = false;
+    if wm::is_tiled(ctx, window) {
+        match settings.tiled {
+            TiledDrag::Float => wm::set_floating(ctx, window, true),
+            TiledDrag::Swap => swap_on_drop = true,
+        }
+    }
+
+    Some(Drag {
+        window,
+        kind,
+        button,
+        start_point: point,
+        start_rect,
+        swap_on_drop,
+    })
+}
+
+pub(crate) fn update_drag(drag: &Drag, point: Point) {
+    let rect = drag_rect(drag.kind, drag.start_rect, drag.start_point, point);
+    drag.window.set_rect(rect);
+}
+
+pub(crate) fn end_drag<A>(ctx: &Context<A>, drag: &Drag)
+where
+    A: Allocator + Copy,
+{
+    if drag.swap_on_drop {
+        wm::drop_window(ctx, drag.window, drag.start_rect);
+    }
+}
+
+/// Rect of window that had `start_rect` when drag started at `start_point` and cursor is at
+/// `point` now. Resizing moves the bottom right corner.
+pub fn drag_rect(kind: DragKind, start_rect: Rect, start_point: Point, point: Point) -> Rect {
+    let dx = point.x - start_point.x;
+    let dy = point.y - start_point.y;
+
+    match kind {
+        DragKind::Move => Rect {
+            x: start_rect.x + dx,
+            y: start_rect.y + dy,
+            ..start_rect
+        },
+        DragKind::Resize => Rect {
+            width: (start_rect.width + dx).max(MIN_DRAG_SIZE),
+            height: (start_rect.height + dy).max(MIN_DRAG_SIZE),
+            ..start_rect
+        },
+    }
+}
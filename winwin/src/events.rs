@@ -1,23 +1,32 @@
 use allocator_api2::alloc::Allocator;
 use core::slice;
 use std::alloc;
+use std::collections::HashSet;
 use std::mem::MaybeUninit;
 use std::sync::mpsc::{self, sync_channel};
-use std::sync::mpsc::{Receiver, SyncSender};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, SyncSender};
+use std::sync::{Arc, Mutex};
 use std::thread::JoinHandle;
 use std::thread::{self};
+use std::time::{Duration, Instant};
 use windows::Win32::Foundation::*;
 use windows::Win32::Storage::FileSystem::*;
 use windows::Win32::System::LibraryLoader::GetModuleHandleA;
 use windows::Win32::System::Pipes::*;
 use windows::Win32::System::Threading::*;
 use windows::Win32::System::IO::*;
+use windows::Win32::UI::Accessibility::*;
 use windows::Win32::UI::WindowsAndMessaging::*;
-use winwin_common::{ClientEvent, SyncHandle};
+use winwin_common::{
+    ClientEvent, MouseButton, MouseDelta, Point, Rect, SyncHandle, WheelDelta, WindowKind,
+};
 
 use windows::core::{s, PCSTR};
 
-use crate::{wm, Context, Input, KeyState, Monitor, Window};
+use crate::{
+    drag, replay_keystrokes, wm, ChordId, ChordOutcome, Context, Input, KeyEventKind, KeyState,
+    Monitor, MouseAction, MouseInput, Window,
+};
 pub use winwin_common::KBDelta;
 
 const THREAD_POOL_SIZE: usize = 2;
@@ -31,20 +40,95 @@ extern "system" {
     fn shell_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT;
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum KeyboardOp {
     InterceptKeypress,
     DoNothing,
 }
 
+/// Decision about keystroke or mouse button with sequence number `seq`.
+#[derive(Debug, Clone, Copy)]
+pub struct KeyboardReply {
+    pub seq: u64,
+    pub op: KeyboardOp,
+}
+
+/// How long keyboard hook waits for event loop to decide whether keystroke is intercepted.
+/// Keyboard input of the entire system is blocked while it waits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyboardTimeout {
+    /// Wait as long as it takes, a stalled event loop freezes keyboard input.
+    Never,
+    /// Let keystroke through after the timeout.
+    PassThrough(Duration),
+    /// Drop keystroke after the timeout.
+    Intercept(Duration),
+}
+
+impl Default for KeyboardTimeout {
+    fn default() -> Self {
+        // Windows removes hooks that keep exceeding `LowLevelHooksTimeout`, 300ms by default.
+        Self::PassThrough(Duration::from_millis(200))
+    }
+}
+
+impl KeyboardTimeout {
+    fn timeout(&self) -> Option<Duration> {
+        match self {
+            Self::Never => None,
+            Self::PassThrough(timeout) | Self::Intercept(timeout) => Some(*timeout),
+        }
+    }
+
+    fn fallback(&self) -> KeyboardOp {
+        match self {
+            Self::Intercept(_) => KeyboardOp::InterceptKeypress,
+            Self::Never | Self::PassThrough(_) => KeyboardOp::DoNothing,
+        }
+    }
+}
+
+/// Sets how long keyboard hook waits for decisions about keystrokes. Has to be called before
+/// `EventQueue` is created.
+pub fn set_keyboard_timeout<A>(ctx: &Context<A>, timeout: KeyboardTimeout)
+where
+    A: Allocator + Copy,
+{
+    ctx.cache.set_keyboard_timeout(timeout);
+}
+
 pub enum Event<A: Allocator> {
     KeyPress(Input<A>),
+    KeyRelease(Input<A>),
+    /// Auto-repeat of a held key.
+    KeyRepeat(Input<A>),
     WindowOpen(Window, Monitor),
     WindowClose(Window, Monitor),
+    WindowTitleChanged(Window),
+    WindowMinimized(Window, Monitor),
+    WindowRestored(Window, Monitor),
+    /// Sequence registered with `add_chord` was completed.
+    Chord(ChordId),
+    /// Mouse button went up or down. Buttons used for window drags are reported too, they never
+    /// reach apps.
+    MouseButton(MouseInput<A>),
+    MouseWheel(MouseInput<A>),
+    /// Cursor moved while intercepted mouse button is held, e.g. during window drag.
+    MouseMove(Point),
+    /// Suspend key was pressed, carries whether winwin is suspended now. See `set_suspend_key`.
+    SuspendChanged(bool),
 }
 
 pub struct EventQueue {
     client_event_rx: Receiver<ClientEvent>,
-    keyboard_tx: SyncSender<KeyboardOp>,
+    keyboard_tx: SyncSender<KeyboardReply>,
+    pending_titles: Arc<PendingTitles>,
+    // Window being dragged by the user and rect it had before the drag started.
+    drag_origin: Option<(Window, Rect)>,
+    // Window under cursor that will be focused once deadline passes.
+    pending_hover: Option<(Window, Instant)>,
+    // Window being moved or resized with mouse, see `set_mouse_drag`.
+    mouse_drag: Option<drag::Drag>,
 
     // Used for shutdown and cleanup.
     iocp_handle: HANDLE,
@@ -61,13 +145,27 @@ impl EventQueue {
 
         let iocp = create_io_completion_port();
 
-        let (kb_tx, kb_rx) = mpsc::sync_channel(0);
+        // Replies are never waited for, late and duplicate ones are queued until hook discards
+        // them.
+        let (kb_tx, kb_rx) = mpsc::sync_channel(16);
+        let keyboard_timeout = ctx.cache.keyboard_timeout();
+        let track_hover = ctx.cache.focus_follows_mouse().is_some();
         let (hook_thread_id_tx, hook_thread_id_rx) = sync_channel(0);
         let keyboard_hook_tx = tx.clone();
-
-        let hook_thread_handle =
-            thread::spawn(|| unsafe { install_hooks(keyboard_hook_tx, kb_rx, hook_thread_id_tx) });
-        let pipe_server_handle = thread::spawn(move || unsafe { install_pipe_server(tx, iocp) });
+        let pending_titles = Arc::new(PendingTitles::default());
+        let pool_pending_titles = pending_titles.clone();
+
+        let hook_thread_handle = thread::spawn(move || unsafe {
+            install_hooks(
+                keyboard_hook_tx,
+                kb_rx,
+                keyboard_timeout,
+                track_hover,
+                hook_thread_id_tx,
+            )
+        });
+        let pipe_server_handle =
+            thread::spawn(move || unsafe { install_pipe_server(tx, iocp, pool_pending_titles) });
 
         // This nonsense in necessary because Rust's ThreadId has nothing to do with actual thread id.
         let hook_thread_id = hook_thread_id_rx.recv().unwrap();
@@ -77,6 +175,10 @@ impl EventQueue {
         Self {
             client_event_rx: rx,
             keyboard_tx: kb_tx,
+            pending_titles,
+            drag_origin: None,
+            pending_hover: None,
+            mouse_drag: None,
 
             iocp_handle: *iocp,
             join_handles: [hook_thread_handle, pipe_server_handle],
@@ -92,34 +194,224 @@ impl EventQueue {
         loop {
             ctx.arena.reset();
 
-            let event = self.client_event_rx.recv().unwrap();
+            let hover_deadline = self.pending_hover.map(|(_, deadline)| deadline);
+            let deadline = match (hover_deadline, ctx.cache.chord_deadline()) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+
+            let event = match deadline {
+                Some(deadline) => {
+                    let timeout = deadline.saturating_duration_since(Instant::now());
+                    match self.client_event_rx.recv_timeout(timeout) {
+                        Ok(event) => event,
+                        Err(RecvTimeoutError::Timeout) => {
+                            self.handle_deadlines(ctx);
+                            continue;
+                        }
+                        Err(RecvTimeoutError::Disconnected) => panic!("event channel disconnected"),
+                    }
+                }
+                None => self.client_event_rx.recv().unwrap(),
+            };
+
             match event {
-                ClientEvent::Keyboard(kb_delta) => {
-                    let input = ctx
-                        .cache
-                        .update_input(ctx, kb_delta, self.keyboard_tx.clone());
-                    return Event::KeyPress(input);
+                ClientEvent::Keyboard(kb_delta, seq) => {
+                    let input =
+                        ctx.cache
+                            .update_input(ctx, kb_delta, seq, self.keyboard_tx.clone());
+
+                    let is_suspend_key = input.kind() == KeyEventKind::Down
+                        && ctx
+                            .cache
+                            .suspend_key()
+                            .is_some_and(|combo| input.completes_no_intercept(&combo.keys()));
+                    if is_suspend_key {
+                        input.intercept();
+                        ctx.cache.set_held_suspend_trigger(Some(input.key()));
+                        return Event::SuspendChanged(ctx.cache.toggle_suspended());
+                    }
+                    // Apps never saw the trigger go down, they must not see its repeats and
+                    // release either.
+                    if ctx.cache.held_suspend_trigger() == Some(input.key()) {
+                        input.intercept();
+                        if input.kind() == KeyEventKind::Up {
+                            ctx.cache.set_held_suspend_trigger(None);
+                        }
+                        continue;
+                    }
+                    // Dropped input lets keystroke through.
+                    if ctx.cache.is_input_disabled() {
+                        continue;
+                    }
+
+                    let outcome = ctx.cache.feed_chords(kb_delta, Instant::now());
+                    if matches!(outcome, ChordOutcome::PassThrough) {
+                        return match input.kind() {
+                            KeyEventKind::Down => Event::KeyPress(input),
+                            KeyEventKind::Up => Event::KeyRelease(input),
+                            KeyEventKind::Repeat => Event::KeyRepeat(input),
+                        };
+                    }
+
+                    // Hook thread has to be unblocked before anything is replayed.
+                    input.intercept();
+                    drop(input);
+                    replay_keystrokes(&ctx.cache.take_chord_replay());
+
+                    if let ChordOutcome::Matched(id) = outcome {
+                        return Event::Chord(id);
+                    }
+                }
+                ClientEvent::MouseButton(mouse_delta, seq) => {
+                    let action = MouseAction::Button(mouse_delta.button, mouse_delta.button_state);
+                    let input = ctx.cache.mouse_input(
+                        ctx,
+                        action,
+                        mouse_delta.point,
+                        seq,
+                        self.keyboard_tx.clone(),
+                    );
+                    // Drag that is going on is finished even when input is disabled.
+                    let disabled = ctx.cache.is_input_disabled();
+                    if (!disabled || self.mouse_drag.is_some())
+                        && self.handle_mouse_button(ctx, mouse_delta)
+                    {
+                        input.intercept();
+                    }
+                    if disabled {
+                        continue;
+                    }
+                    return Event::MouseButton(input);
+                }
+                ClientEvent::MouseWheel(wheel_delta, seq) => {
+                    let action = if wheel_delta.horizontal {
+                        MouseAction::HorizontalWheel(wheel_delta.delta)
+                    } else {
+                        MouseAction::Wheel(wheel_delta.delta)
+                    };
+                    let input = ctx.cache.mouse_input(
+                        ctx,
+                        action,
+                        wheel_delta.point,
+                        seq,
+                        self.keyboard_tx.clone(),
+                    );
+                    if ctx.cache.is_input_disabled() {
+                        continue;
+                    }
+                    return Event::MouseWheel(input);
+                }
+                ClientEvent::MouseMove(point) => {
+                    if let Some(mouse_drag) = &self.mouse_drag {
+                        drag::update_drag(mouse_drag, point);
+                    }
+                    if ctx.cache.is_suspended() {
+                        continue;
+                    }
+                    return Event::MouseMove(point);
                 }
                 ClientEvent::WindowOpen(window_handle, monitor_handle) => {
                     let window = Window::from(window_handle);
                     let monitor = Monitor::from(monitor_handle);
+                    if ctx.cache.window_kind(window) != WindowKind::Managed {
+                        continue;
+                    }
                     ctx.cache.add_window_to_queue(window, monitor);
+                    // Cache is kept up to date but nothing is tiled while suspended.
+                    if ctx.cache.is_suspended() {
+                        continue;
+                    }
                     return Event::WindowOpen(window, monitor);
                 }
                 ClientEvent::WindowClose(window_handle, monitor_handle) => {
                     let window = Window::from(window_handle);
                     let monitor = Monitor::from(monitor_handle);
                     ctx.cache.remove_window_from_queue(window, monitor);
+                    if ctx.cache.is_suspended() {
+                        continue;
+                    }
                     return Event::WindowClose(window, monitor);
                 }
+                ClientEvent::WindowTitleChanged(window_handle) => {
+                    // Any change from now on has to be reported again.
+                    self.pending_titles.remove(window_handle);
+
+                    let window = Window::from(window_handle);
+                    if !ctx.cache.contains_window(window) || ctx.cache.is_suspended() {
+                        continue;
+                    }
+                    return Event::WindowTitleChanged(window);
+                }
+                ClientEvent::WindowMinimized(window_handle) => {
+                    let window = Window::from(window_handle);
+                    if !ctx.cache.contains_window(window) {
+                        continue;
+                    }
+                    ctx.cache.set_minimized(window, true);
+                    if ctx.cache.is_suspended() {
+                        continue;
+                    }
+                    let monitor = wm::get_monitor_with_window(ctx, window);
+                    return Event::WindowMinimized(window, monitor);
+                }
+                ClientEvent::WindowRestored(window_handle) => {
+                    let window = Window::from(window_handle);
+                    if !ctx.cache.contains_window(window) {
+                        continue;
+                    }
+                    ctx.cache.set_minimized(window, false);
+                    if ctx.cache.is_suspended() {
+                        continue;
+                    }
+                    let monitor = wm::get_monitor_with_window(ctx, window);
+                    return Event::WindowRestored(window, monitor);
+                }
+                ClientEvent::WindowMoveSizeStart(window_handle) => {
+                    let window = Window::from(window_handle);
+                    if ctx.cache.contains_window(window) && !ctx.cache.is_suspended() {
+                        self.drag_origin = Some((window, window.rect()));
+                    }
+                }
+                ClientEvent::WindowMoveSizeEnd(window_handle) => {
+                    let window = Window::from(window_handle);
+                    if let Some((origin_window, origin_rect)) = self.drag_origin.take() {
+                        if origin_window == window {
+                            wm::drop_window(ctx, window, origin_rect);
+                        }
+                    }
+                }
+                ClientEvent::WindowHovered(window_handle) => {
+                    let window = Window::from(window_handle);
+                    self.pending_hover = ctx
+                        .cache
+                        .focus_follows_mouse()
+                        .filter(|_| !ctx.cache.is_suspended())
+                        .filter(|ffm| wm::is_tiled(ctx, window) && !ffm.ignores(window))
+                        .map(|ffm| (window, Instant::now() + ffm.delay));
+                }
                 ClientEvent::WindowMonitorChanged(window_handle, monitor_handle) => {
                     let window = Window::from(window_handle);
                     let monitor = Monitor::from(monitor_handle);
                     ctx.cache.update_window_queue(monitor, window);
                 }
                 ClientEvent::WindowFocusHanged(window_handle) => {
+                    // Focus could have moved off a fullscreen game, which is usually not managed.
+                    if !ctx.cache.is_suspended() {
+                        wm::apply_deferred_layouts(ctx);
+                    }
+
                     let window = Window::from(window_handle);
-                    let monitor = wm::get_monitor_with_window(ctx, window);
+                    if ctx.cache.window_kind(window) != WindowKind::Managed {
+                        continue;
+                    }
+
+                    // Window could have been ignored when it was created (e.g. it was cloaked)
+                    // and is not cached yet.
+                    let mut monitor = wm::get_monitor_with_window(ctx, window);
+                    if monitor == Monitor::default() {
+                        monitor = wm::get_monitor_with_window_live(ctx, window);
+                    }
                     ctx.cache.update_window_queue(monitor, window);
                 }
                 ClientEvent::MonitorConnected(monitor_handle) => {
@@ -133,6 +425,47 @@ impl EventQueue {
         }
     }
 
+    // Returns true if button has to be intercepted.
+    fn handle_mouse_button<A>(&mut self, ctx: &Context<A>, mouse_delta: MouseDelta) -> bool
+    where
+        A: Allocator + Copy,
+    {
+        match (mouse_delta.button_state, &self.mouse_drag) {
+            (KeyState::Down, None) => {
+                self.mouse_drag = drag::begin_drag(ctx, mouse_delta.button, mouse_delta.point);
+                self.mouse_drag.is_some()
+            }
+            (KeyState::Up, Some(mouse_drag)) if mouse_drag.button == mouse_delta.button => {
+                drag::update_drag(mouse_drag, mouse_delta.point);
+                drag::end_drag(ctx, mouse_drag);
+                self.mouse_drag = None;
+                true
+            }
+            // Other buttons are swallowed while dragging.
+            (_, Some(_)) => true,
+            (KeyState::Up, None) => false,
+        }
+    }
+
+    fn handle_deadlines<A>(&mut self, ctx: &Context<A>)
+    where
+        A: Allocator + Copy,
+    {
+        let now = Instant::now();
+
+        if let Some((window, deadline)) = self.pending_hover {
+            if now >= deadline {
+                self.pending_hover = None;
+                if wm::get_focused_window(ctx) != window {
+                    window.focus();
+                }
+            }
+        }
+
+        ctx.cache.tick_chords(now);
+        replay_keystrokes(&ctx.cache.take_chord_replay());
+    }
+
     // `shutdown` must be called explicitly before application can exit.
     pub fn shutdown(self) {
         unsafe {
@@ -151,13 +484,15 @@ impl EventQueue {
 
 unsafe fn install_hooks(
     tx: SyncSender<ClientEvent>,
-    rx: Receiver<KeyboardOp>,
+    rx: Receiver<KeyboardReply>,
+    timeout: KeyboardTimeout,
+    track_hover: bool,
     thread_id_tx: SyncSender<u32>,
 ) {
     thread_id_tx
         .send(GetCurrentThreadId())
         .expect("main thread is waiting for this id");
-    KB_HANDLER.init(tx, rx);
+    KB_HANDLER.init(tx, rx, timeout, track_hover);
 
     let main_h_instance =
         GetModuleHandleA(None).expect("loading handle to current exe should always succseed");
@@ -168,6 +503,8 @@ unsafe fn install_hooks(
         0,
     )
     .unwrap();
+    let mouse_hook =
+        SetWindowsHookExA(WH_MOUSE_LL, Some(low_level_mouse_proc), main_h_instance, 0).unwrap();
 
     let dll_name = s!("hooks.dll");
     let h_instance =
@@ -176,13 +513,26 @@ unsafe fn install_hooks(
     let cbt_hook = SetWindowsHookExA(WH_CBT, Some(cbt_proc), h_instance, 0).unwrap();
     let shell_hook = SetWindowsHookExA(WH_SHELL, Some(shell_proc), h_instance, 0).unwrap();
 
+    // Out of context events are delivered to this thread while it waits in `GetMessageA`.
+    let move_size_hook = SetWinEventHook(
+        EVENT_SYSTEM_MOVESIZESTART,
+        EVENT_SYSTEM_MOVESIZEEND,
+        None,
+        Some(win_event_proc),
+        0,
+        0,
+        WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS,
+    );
+
     // GetMessageA will return once PostThreadMessageA in `EventQueue::shutdown` posts a message.
     let mut msg = MSG::default();
     let _ = GetMessageA(&mut msg as *mut _, None, 0, 0);
 
     let _ = UnhookWindowsHookEx(kb_hook);
+    let _ = UnhookWindowsHookEx(mouse_hook);
     let _ = UnhookWindowsHookEx(cbt_hook);
     let _ = UnhookWindowsHookEx(shell_hook);
+    let _ = UnhookWinEvent(move_size_hook);
 
     tracing::trace!("hooks unloaded");
 }
@@ -191,7 +541,16 @@ static mut KB_HANDLER: KeyboardHandler = KeyboardHandler::new();
 
 struct KeyboardHandler {
     sender: MaybeUninit<SyncSender<ClientEvent>>,
-    receiver: MaybeUninit<Receiver<KeyboardOp>>,
+    receiver: MaybeUninit<Receiver<KeyboardReply>>,
+    timeout: KeyboardTimeout,
+    // Sequence number of the last keystroke sent to main thread.
+    seq: u64,
+    // Whether windows under cursor are reported, only focus follows mouse needs them.
+    track_hover: bool,
+    // Last top level window reported as being under cursor.
+    hovered_window: usize,
+    // Button whose press was intercepted, cursor moves are reported until it is released.
+    captured_button: Option<MouseButton>,
 }
 
 impl KeyboardHandler {
@@ -199,18 +558,31 @@ impl KeyboardHandler {
         Self {
             sender: MaybeUninit::uninit(),
             receiver: MaybeUninit::uninit(),
+            timeout: KeyboardTimeout::Never,
+            seq: 0,
+            track_hover: false,
+            hovered_window: 0,
+            captured_button: None,
         }
     }
-    fn init(&mut self, tx: SyncSender<ClientEvent>, rx: Receiver<KeyboardOp>) {
+    fn init(
+        &mut self,
+        tx: SyncSender<ClientEvent>,
+        rx: Receiver<KeyboardReply>,
+        timeout: KeyboardTimeout,
+        track_hover: bool,
+    ) {
         self.sender.write(tx);
         self.receiver.write(rx);
+        self.timeout = timeout;
+        self.track_hover = track_hover;
     }
 
     unsafe fn get_sender(&self) -> &SyncSender<ClientEvent> {
         unsafe { self.sender.assume_init_ref() }
     }
 
-    unsafe fn get_receiver(&self) -> &Receiver<KeyboardOp> {
+    unsafe fn get_receiver(&self) -> &Receiver<KeyboardReply> {
         unsafe { self.receiver.assume_init_ref() }
     }
 }
@@ -222,20 +594,41 @@ unsafe extern "system" fn low_level_keyboard_proc(
 ) -> LRESULT {
     if code == HC_ACTION as _ {
         let kb_info = &*(lparam.0 as *const KBDLLHOOKSTRUCT);
+        // Injected keystrokes, replayed and sent ones included, were not typed by the user and
+        // must never trigger bindings.
+        if kb_info.flags.contains(LLKHF_INJECTED) {
+            return CallNextHookEx(None, code, wparam, lparam);
+        }
+
         let kb_delta = KBDelta {
             vk_code: kb_info.vkCode as _,
             key_state: KeyState::from(wparam),
+            time: kb_info.time,
         };
 
-        let event = ClientEvent::Keyboard(kb_delta);
-        let tx = KB_HANDLER.get_sender();
-        tx.send(event)
-            .expect("main thread must still be around at this point");
+        let handler = &mut *std::ptr::addr_of_mut!(KB_HANDLER);
+        handler.seq += 1;
+        let seq = handler.seq;
 
-        let rx = KB_HANDLER.get_receiver();
-        let op = rx
-            .recv()
-            .expect("hook thread must still be around at this point");
+        let event = ClientEvent::Keyboard(kb_delta, seq);
+        // Full queue means event loop is stalled already, waiting would freeze the keyboard.
+        if handler.get_sender().try_send(event).is_err() {
+            tracing::warn!(seq, "event queue is full, keystroke passed through");
+            return CallNextHookEx(None, code, wparam, lparam);
+        }
+
+        let started = Instant::now();
+        let timeout = handler.timeout;
+        let op = await_keyboard_reply(handler.get_receiver(), seq, timeout.timeout())
+            .unwrap_or_else(|| {
+                tracing::warn!(
+                    seq,
+                    waited_ms = started.elapsed().as_millis() as u64,
+                    "no decision for keystroke in time, falling back to {:?}",
+                    timeout
+                );
+                timeout.fallback()
+            });
 
         if matches!(op, KeyboardOp::InterceptKeypress) {
             return LRESULT(-1);
@@ -245,13 +638,209 @@ unsafe extern "system" fn low_level_keyboard_proc(
     return CallNextHookEx(None, code, wparam, lparam);
 }
 
-unsafe fn install_pipe_server(tx: SyncSender<ClientEvent>, iocp: SyncHandle) {
-    let mut pool = IocpWorkerPool::new(iocp, tx);
+/// Waits for reply to keystroke `seq`. Replies to earlier keystrokes that arrived too late are
+/// discarded. Returns `None` once `timeout` passes or event loop is gone.
+pub(crate) fn await_keyboard_reply(
+    rx: &Receiver<KeyboardReply>,
+    seq: u64,
+    timeout: Option<Duration>,
+) -> Option<KeyboardOp> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    loop {
+        let reply = match deadline {
+            Some(deadline) => rx
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                .ok()?,
+            None => rx.recv().ok()?,
+        };
+
+        if reply.seq == seq {
+            return Some(reply.op);
+        }
+    }
+}
+
+unsafe extern "system" fn low_level_mouse_proc(
+    code: i32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    if code != HC_ACTION as _ {
+        return CallNextHookEx(None, code, wparam, lparam);
+    }
+
+    let mouse_info = &*(lparam.0 as *const MSLLHOOKSTRUCT);
+    let handler = &mut *std::ptr::addr_of_mut!(KB_HANDLER);
+
+    // Mouse moves are far too frequent to report, we only report when cursor enters another
+    // window or when a drag is going on. Moves must never wait on main thread or the cursor would
+    // stutter.
+    if wparam.0 == WM_MOUSEMOVE as usize {
+        if handler.captured_button.is_some() {
+            // Dropped moves do not matter, next one carries absolute position anyway.
+            let event = ClientEvent::MouseMove(Point::from(mouse_info.pt));
+            let _ = handler.get_sender().try_send(event);
+            return CallNextHookEx(None, code, wparam, lparam);
+        }
+
+        if !handler.track_hover {
+            return CallNextHookEx(None, code, wparam, lparam);
+        }
+
+        let hwnd = GetAncestor(WindowFromPoint(mouse_info.pt), GA_ROOT);
+        if hwnd.0 as usize != handler.hovered_window {
+            let event = ClientEvent::WindowHovered(hwnd.0 as _);
+            // Should the queue be full we report this window again on next move.
+            if handler.get_sender().try_send(event).is_ok() {
+                handler.hovered_window = hwnd.0 as _;
+            }
+        }
+
+        return CallNextHookEx(None, code, wparam, lparam);
+    }
+
+    let message = wparam.0 as u32;
+    let point = Point::from(mouse_info.pt);
+    let button = mouse_button(message, mouse_info.mouseData);
+    let is_wheel = message == WM_MOUSEWHEEL || message == WM_MOUSEHWHEEL;
+    if button.is_none() && !is_wheel {
+        return CallNextHookEx(None, code, wparam, lparam);
+    }
+
+    handler.seq += 1;
+    let seq = handler.seq;
+    let event = match button {
+        Some((button, button_state)) => ClientEvent::MouseButton(
+            MouseDelta {
+                button,
+                button_state,
+                point,
+                time: mouse_info.time,
+            },
+            seq,
+        ),
+        None => ClientEvent::MouseWheel(
+            WheelDelta {
+                // High word of `mouse_data` is the signed rotation.
+                delta: (mouse_info.mouseData >> 16) as u16 as i16,
+                horizontal: message == WM_MOUSEHWHEEL,
+                point,
+                time: mouse_info.time,
+            },
+            seq,
+        ),
+    };
+
+    if handler.get_sender().try_send(event).is_err() {
+        tracing::warn!(seq, "event queue is full, mouse event passed through");
+        return CallNextHookEx(None, code, wparam, lparam);
+    }
+
+    // Unlike keys, mouse events always pass through on timeout.
+    let op = await_keyboard_reply(handler.get_receiver(), seq, handler.timeout.timeout())
+        .unwrap_or(KeyboardOp::DoNothing);
+
+    match button {
+        Some((button, KeyState::Down)) if op == KeyboardOp::InterceptKeypress => {
+            handler.captured_button.get_or_insert(button);
+        }
+        Some((button, KeyState::Up)) if handler.captured_button == Some(button) => {
+            handler.captured_button = None;
+        }
+        _ => {}
+    }
+
+    if op == KeyboardOp::InterceptKeypress {
+        return LRESULT(1);
+    }
+
+    return CallNextHookEx(None, code, wparam, lparam);
+}
+
+fn mouse_button(message: u32, mouse_data: u32) -> Option<(MouseButton, KeyState)> {
+    // High word of `mouse_data` tells which X button it is.
+    let x_button = if (mouse_data >> 16) as u16 == XBUTTON1 {
+        MouseButton::X1
+    } else {
+        MouseButton::X2
+    };
+
+    let button = match message {
+        WM_LBUTTONDOWN => (MouseButton::Left, KeyState::Down),
+        WM_LBUTTONUP => (MouseButton::Left, KeyState::Up),
+        WM_RBUTTONDOWN => (MouseButton::Right, KeyState::Down),
+        WM_RBUTTONUP => (MouseButton::Right, KeyState::Up),
+        WM_MBUTTONDOWN => (MouseButton::Middle, KeyState::Down),
+        WM_MBUTTONUP => (MouseButton::Middle, KeyState::Up),
+        WM_XBUTTONDOWN => (x_button, KeyState::Down),
+        WM_XBUTTONUP => (x_button, KeyState::Up),
+        _ => return None,
+    };
+    Some(button)
+}
+
+/// Windows that have `WindowTitleChanged` waiting in the event channel. Title is read only once the
+/// event is handled so repeated changes can be dropped until then. This keeps windows with
+/// flickering titles from flooding the channel.
+#[derive(Default)]
+struct PendingTitles {
+    windows: Mutex<HashSet<usize>>,
+}
+
+impl PendingTitles {
+    // Returns false if event for this window is already pending.
+    fn insert(&self, window_handle: usize) -> bool {
+        match self.windows.lock() {
+            Ok(mut windows) => windows.insert(window_handle),
+            Err(_) => true,
+        }
+    }
+
+    fn remove(&self, window_handle: usize) {
+        if let Ok(mut windows) = self.windows.lock() {
+            windows.remove(&window_handle);
+        }
+    }
+}
+
+unsafe extern "system" fn win_event_proc(
+    _hook: HWINEVENTHOOK,
+    event: u32,
+    hwnd: HWND,
+    id_object: i32,
+    id_child: i32,
+    _event_thread: u32,
+    _event_time: u32,
+) {
+    if id_object != OBJID_WINDOW.0 || id_child != CHILDID_SELF as i32 {
+        return;
+    }
+
+    let event = match event {
+        EVENT_SYSTEM_MOVESIZESTART => ClientEvent::WindowMoveSizeStart(hwnd.0 as _),
+        EVENT_SYSTEM_MOVESIZEEND => ClientEvent::WindowMoveSizeEnd(hwnd.0 as _),
+        _ => return,
+    };
+
+    // This runs on the hook thread, blocking here would stall keyboard and mouse input.
+    let tx = KB_HANDLER.get_sender();
+    if let Err(e) = tx.try_send(event) {
+        tracing::warn!(?e, "move or resize event dropped");
+    }
+}
+
+unsafe fn install_pipe_server(
+    tx: SyncSender<ClientEvent>,
+    iocp: SyncHandle,
+    pending_titles: Arc<PendingTitles>,
+) {
+    let mut pool = IocpWorkerPool::new(iocp, tx, pending_titles);
     pool.start_workers_and_accept_connections();
 }
 
 struct IocpWorkerPool {
     event_tx: SyncSender<ClientEvent>,
+    pending_titles: Arc<PendingTitles>,
     io_objects_pool: Receiver<usize>,
     io_objects_release_channel: Option<SyncSender<usize>>,
 
@@ -263,7 +852,11 @@ struct IocpWorkerPool {
 }
 
 impl IocpWorkerPool {
-    fn new(iocp: SyncHandle, event_tx: SyncSender<ClientEvent>) -> Self {
+    fn new(
+        iocp: SyncHandle,
+        event_tx: SyncSender<ClientEvent>,
+        pending_titles: Arc<PendingTitles>,
+    ) -> Self {
         let pipe_instance_count = PIPE_INSTANCES_PER_WORKER * THREAD_POOL_SIZE;
         let (io_objects_tx, io_objects_rx) = sync_channel(pipe_instance_count);
 
@@ -304,6 +897,7 @@ impl IocpWorkerPool {
 
         Self {
             event_tx,
+            pending_titles,
             io_objects_pool: io_objects_rx,
             io_objects_release_channel: Some(io_objects_tx),
 
@@ -321,9 +915,12 @@ impl IocpWorkerPool {
         thread::scope(|s| {
             for _ in 0..THREAD_POOL_SIZE {
                 let event_tx = self.event_tx.clone();
+                let pending_titles = self.pending_titles.clone();
                 let release_channel = io_objects_release_channel.clone();
                 let iocp = self.iocp;
-                s.spawn(move || unsafe { handle_iocp(iocp, release_channel, event_tx) });
+                s.spawn(move || unsafe {
+                    handle_iocp(iocp, release_channel, event_tx, pending_titles)
+                });
             }
 
             // We drop this sender so that only senders left are ones owned by woker threads, this
@@ -409,6 +1006,7 @@ unsafe fn handle_iocp(
     iocp: SyncHandle,
     io_objects_release_channel: SyncSender<usize>,
     event_tx: SyncSender<ClientEvent>,
+    pending_titles: Arc<PendingTitles>,
 ) {
     let mut completion_key = 0;
     let mut bytes_transferred = 0;
@@ -460,9 +1058,19 @@ unsafe fn handle_iocp(
             State::ReadEnqueued => {
                 let client_event: ClientEvent =
                     bincode::deserialize(&io_data.buffer[..bytes_transferred as usize]).unwrap();
-                event_tx
-                    .send(client_event)
-                    .expect("other end should not quit before this thread");
+
+                let is_duplicate = match client_event {
+                    ClientEvent::WindowTitleChanged(window_handle) => {
+                        !pending_titles.insert(window_handle)
+                    }
+                    _ => false,
+                };
+
+                if !is_duplicate {
+                    event_tx
+                        .send(client_event)
+                        .expect("other end should not quit before this thread");
+                }
 
                 io_objects_release_channel
                     .send(io_data as *mut _ as _)
@@ -515,3 +1123,83 @@ fn create_io_completion_port() -> SyncHandle {
     };
     SyncHandle(iocp)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Option<Duration> = Some(Duration::from_millis(50));
+
+    fn reply(seq: u64, op: KeyboardOp) -> KeyboardReply {
+        KeyboardReply { seq, op }
+    }
+
+    #[test]
+    fn reply_in_time() {
+        let (tx, rx) = sync_channel(1);
+        tx.send(reply(1, KeyboardOp::InterceptKeypress)).unwrap();
+        assert_eq!(
+            await_keyboard_reply(&rx, 1, TIMEOUT),
+            Some(KeyboardOp::InterceptKeypress)
+        );
+    }
+
+    #[test]
+    fn reply_from_other_thread() {
+        let (tx, rx) = sync_channel(1);
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            tx.send(reply(1, KeyboardOp::DoNothing)).unwrap();
+            // Keeps the channel open so only the reply can end the wait.
+            thread::sleep(Duration::from_millis(100));
+        });
+        assert_eq!(
+            await_keyboard_reply(&rx, 1, Some(Duration::from_secs(5))),
+            Some(KeyboardOp::DoNothing)
+        );
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn timeout() {
+        let (_tx, rx) = sync_channel::<KeyboardReply>(1);
+        let start = Instant::now();
+        assert_eq!(await_keyboard_reply(&rx, 1, TIMEOUT), None);
+        assert!(start.elapsed() >= TIMEOUT.unwrap());
+    }
+
+    #[test]
+    fn late_reply_to_earlier_keystroke_is_discarded() {
+        let (tx, rx) = sync_channel(2);
+        tx.send(reply(1, KeyboardOp::InterceptKeypress)).unwrap();
+        tx.send(reply(2, KeyboardOp::DoNothing)).unwrap();
+        assert_eq!(
+            await_keyboard_reply(&rx, 2, TIMEOUT),
+            Some(KeyboardOp::DoNothing)
+        );
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn only_late_replies_time_out() {
+        let (tx, rx) = sync_channel(2);
+        tx.send(reply(1, KeyboardOp::InterceptKeypress)).unwrap();
+        assert_eq!(await_keyboard_reply(&rx, 2, TIMEOUT), None);
+    }
+
+    #[test]
+    fn disconnected_sender() {
+        let (tx, rx) = sync_channel::<KeyboardReply>(1);
+        drop(tx);
+        assert_eq!(await_keyboard_reply(&rx, 1, TIMEOUT), None);
+        assert_eq!(await_keyboard_reply(&rx, 1, None), None);
+    }
+
+    #[test]
+    fn disconnected_after_late_reply() {
+        let (tx, rx) = sync_channel(1);
+        tx.send(reply(1, KeyboardOp::InterceptKeypress)).unwrap();
+        drop(tx);
+        assert_eq!(await_keyboard_reply(&rx, 2, None), None);
+    }
+}
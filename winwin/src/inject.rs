@@ -0,0 +1,242 @@
+use allocator_api2::alloc::Allocator;
+use windows::Win32::UI::Input::KeyboardAndMouse::*;
+use winwin_common::{KBDelta, Key, KeyCombo, KeyState};
+
+use crate::Context;
+
+// Marks input sent by us so other hooks can tell it apart.
+const INJECTED_TAG: usize = 0x7769_6e77;
+// Unassigned key, pressing it between Alt or Win going down and up keeps Windows from treating
+// them as released alone.
+pub(crate) const MASK_VK_CODE: u8 = 0xE8;
+
+/// Single input record sent with `SendInput`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keystroke {
+    /// Virtual key code.
+    Key(u8, KeyState),
+    /// UTF-16 code unit, typed as is regardless of keyboard layout.
+    Unicode(u16, KeyState),
+}
+
+/// Sends `combo` to focused window, e.g. to remap `Alt+H` to `Left`. Modifiers held at the time are
+/// released for the duration so they do not combine with it.
+pub fn send_combo<A>(ctx: &Context<A>, combo: &KeyCombo)
+where
+    A: Allocator + Copy,
+{
+    send_keystrokes(&combo_keystrokes(combo, &ctx.cache.pressed_keys()));
+}
+
+/// Types `text` into focused window. Held modifiers are released for the duration like with
+/// `send_combo`.
+pub fn send_text<A>(ctx: &Context<A>, text: &str)
+where
+    A: Allocator + Copy,
+{
+    send_keystrokes(&text_keystrokes(text, &ctx.cache.pressed_keys()));
+}
+
+/// Keystrokes pressing and releasing `combo` while `held` keys are held.
+pub fn combo_keystrokes(combo: &KeyCombo, held: &[Key]) -> Vec<Keystroke> {
+    let keys = combo.keys();
+    let mut keystrokes: Vec<Keystroke> = keys
+        .iter()
+        .map(|k| Keystroke::Key(k.to_vk_code(), KeyState::Down))
+        .collect();
+    keystrokes.extend(
+        keys.iter()
+            .rev()
+            .map(|k| Keystroke::Key(k.to_vk_code(), KeyState::Up)),
+    );
+    with_modifiers_released(held, keystrokes)
+}
+
+/// Keystrokes typing `text` while `held` keys are held. New lines are sent as `Key::Return` since
+/// apps rarely treat typed line feed as one.
+pub fn text_keystrokes(text: &str, held: &[Key]) -> Vec<Keystroke> {
+    let mut keystrokes = Vec::new();
+    let mut units = [0; 2];
+    for c in text.chars() {
+        if c == '\n' {
+            let vk_code = Key::Return.to_vk_code();
+            keystrokes.push(Keystroke::Key(vk_code, KeyState::Down));
+            keystrokes.push(Keystroke::Key(vk_code, KeyState::Up));
+            continue;
+        }
+
+        for unit in c.encode_utf16(&mut units) {
+            keystrokes.push(Keystroke::Unicode(*unit, KeyState::Down));
+            keystrokes.push(Keystroke::Unicode(*unit, KeyState::Up));
+        }
+    }
+    with_modifiers_released(held, keystrokes)
+}
+
+fn with_modifiers_released(held: &[Key], keystrokes: Vec<Keystroke>) -> Vec<Keystroke> {
+    let modifiers: Vec<u8> = held
+        .iter()
+        .filter(|k| k.is_modifier())
+        .map(|k| k.to_vk_code())
+        .collect();
+    if modifiers.is_empty() {
+        return keystrokes;
+    }
+
+    let mut all = vec![
+        Keystroke::Key(MASK_VK_CODE, KeyState::Down),
+        Keystroke::Key(MASK_VK_CODE, KeyState::Up),
+    ];
+    all.extend(modifiers.iter().map(|vk| Keystroke::Key(*vk, KeyState::Up)));
+    all.extend(keystrokes);
+    all.extend(
+        modifiers
+            .iter()
+            .map(|vk| Keystroke::Key(*vk, KeyState::Down)),
+    );
+    all
+}
+
+pub(crate) fn replay_keystrokes(deltas: &[KBDelta]) {
+    let keystrokes: Vec<Keystroke> = deltas
+        .iter()
+        .map(|delta| Keystroke::Key(delta.vk_code, delta.key_state))
+        .collect();
+    send_keystrokes(&keystrokes);
+}
+
+pub(crate) fn send_keystrokes(keystrokes: &[Keystroke]) {
+    if keystrokes.is_empty() {
+        return;
+    }
+
+    let inputs: Vec<INPUT> = keystrokes
+        .iter()
+        .map(|keystroke| {
+            let (vk_code, scan, flags, key_state) = match *keystroke {
+                Keystroke::Key(vk_code, key_state) => (vk_code, 0, KEYBD_EVENT_FLAGS(0), key_state),
+                Keystroke::Unicode(unit, key_state) => (0, unit, KEYEVENTF_UNICODE, key_state),
+            };
+            let flags = match key_state {
+                KeyState::Up => flags | KEYEVENTF_KEYUP,
+                KeyState::Down => flags,
+            };
+
+            INPUT {
+                r#type: INPUT_KEYBOARD,
+                Anonymous: INPUT_0 {
+                    ki: KEYBDINPUT {
+                        wVk: VIRTUAL_KEY(vk_code as _),
+                        wScan: scan,
+                        dwFlags: flags,
+                        time: 0,
+                        dwExtraInfo: INJECTED_TAG,
+                    },
+                },
+            }
+        })
+        .collect();
+
+    let sent = unsafe { SendInput(&inputs, std::mem::size_of::<INPUT>() as i32) };
+    if sent as usize != inputs.len() {
+        tracing::warn!("only {} of {} keystrokes were sent", sent, inputs.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn down(key: Key) -> Keystroke {
+        Keystroke::Key(key.to_vk_code(), KeyState::Down)
+    }
+
+    fn up(key: Key) -> Keystroke {
+        Keystroke::Key(key.to_vk_code(), KeyState::Up)
+    }
+
+    fn unicode(unit: u16) -> [Keystroke; 2] {
+        [
+            Keystroke::Unicode(unit, KeyState::Down),
+            Keystroke::Unicode(unit, KeyState::Up),
+        ]
+    }
+
+    const MASK: [Keystroke; 2] = [
+        Keystroke::Key(MASK_VK_CODE, KeyState::Down),
+        Keystroke::Key(MASK_VK_CODE, KeyState::Up),
+    ];
+
+    #[test]
+    fn combo_without_held_keys() {
+        let combo = KeyCombo::new(&[Key::Ctrl, Key::Shift], Key::T);
+        assert_eq!(
+            combo_keystrokes(&combo, &[]),
+            [
+                down(Key::Ctrl),
+                down(Key::Shift),
+                down(Key::T),
+                up(Key::T),
+                up(Key::Shift),
+                up(Key::Ctrl),
+            ]
+        );
+    }
+
+    #[test]
+    fn held_modifiers_are_released_and_pressed_again() {
+        let combo = KeyCombo::new(&[], Key::Left);
+        let held = [Key::AltLeft, Key::H, Key::WinR];
+        let mut expected = MASK.to_vec();
+        expected.extend([
+            up(Key::AltLeft),
+            up(Key::WinR),
+            down(Key::Left),
+            up(Key::Left),
+            down(Key::AltLeft),
+            down(Key::WinR),
+        ]);
+        assert_eq!(combo_keystrokes(&combo, &held), expected);
+    }
+
+    #[test]
+    fn held_non_modifiers_are_left_alone() {
+        let combo = KeyCombo::new(&[], Key::Left);
+        assert_eq!(
+            combo_keystrokes(&combo, &[Key::H]),
+            [down(Key::Left), up(Key::Left)]
+        );
+    }
+
+    #[test]
+    fn text() {
+        let mut expected = unicode('h' as u16).to_vec();
+        expected.extend(unicode('é' as u16));
+        assert_eq!(text_keystrokes("hé", &[]), expected);
+        assert!(text_keystrokes("", &[]).is_empty());
+    }
+
+    #[test]
+    fn surrogate_pairs() {
+        let mut expected = unicode(0xD83D).to_vec();
+        expected.extend(unicode(0xDE00));
+        assert_eq!(text_keystrokes("😀", &[]), expected);
+    }
+
+    #[test]
+    fn new_line_is_return() {
+        let mut expected = unicode('a' as u16).to_vec();
+        expected.extend([down(Key::Return), up(Key::Return)]);
+        expected.extend(unicode('b' as u16));
+        assert_eq!(text_keystrokes("a\nb", &[]), expected);
+    }
+
+    #[test]
+    fn text_with_held_modifiers() {
+        let mut expected = MASK.to_vec();
+        expected.push(up(Key::CtrlLeft));
+        expected.extend(unicode('a' as u16));
+        expected.push(down(Key::CtrlLeft));
+        assert_eq!(text_keystrokes("a", &[Key::CtrlLeft]), expected);
+    }
+}
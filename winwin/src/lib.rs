@@ -5,13 +5,19 @@ use allocator_api2::vec::Vec;
 use std::cell::Cell;
 use std::cell::UnsafeCell;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::collections::VecDeque;
 use std::marker::PhantomData;
 use std::mem::MaybeUninit;
 use std::sync::mpsc::SyncSender;
+use std::time::{Duration, Instant};
 use std::{alloc, ptr::NonNull};
+use windows::Win32::UI::WindowsAndMessaging::{WINDOWPLACEMENT, WINDOW_STYLE};
 
-pub use winwin_common::{Key, KeyState};
+pub use winwin_common::{
+    modifiers_match, Key, KeyCombo, KeyState, Modifier, ModifierMatching, MouseButton,
+    ParseKeyComboError, Point, WindowDescriptor, WindowKind, WindowRule,
+};
 
 mod events;
 pub use events::*;
@@ -19,6 +25,18 @@ pub use events::*;
 mod wm;
 pub use wm::*;
 
+mod chord;
+pub use chord::*;
+
+mod bindings;
+pub use bindings::*;
+
+mod drag;
+pub use drag::*;
+
+mod inject;
+pub use inject::*;
+
 #[macro_export]
 macro_rules! trace_result {
     ($($result:expr),* $(,)?) => {
@@ -209,7 +227,35 @@ pub struct Cache {
 struct InnerCache {
     key_map: KeyMap,
     monitor_layouts: HashMap<Monitor, Layout>,
+    // Monitors whose layout was not applied because they were covered by a fullscreen window.
+    deferred_layouts: HashSet<Monitor>,
+    // Share of monitor width taken by master window in stack layout.
+    master_ratios: HashMap<Monitor, f64>,
     window_queues: VecDeque<(Monitor, VecDeque<Window>)>,
+    window_rules: Vec<WindowRule>,
+    // Minimized windows keep their place in the queue but are skipped by layouts.
+    minimized: HashSet<Window>,
+    // Most recently focused windows across all monitors, front is the focused one.
+    focus_history: VecDeque<Window>,
+    // Process name of the focused window, looked up on first use after focus changes.
+    focused_process: Option<(Window, String)>,
+    // Windows that keep their place in the queue but are left out of layout.
+    floating: HashSet<Window>,
+    // Windows toggled with `toggle_fullscreen` and placement and style they had before.
+    fullscreen: HashMap<Window, (WINDOWPLACEMENT, WINDOW_STYLE)>,
+    focus_follows_mouse: Option<FocusFollowsMouse>,
+    cursor_warp: CursorWarp,
+    navigation: Navigation,
+    chords: ChordMachine,
+    keyboard_timeout: KeyboardTimeout,
+    taps: TapDetector,
+    mouse_drag: Option<MouseDrag>,
+    suspend_key: Option<KeyCombo>,
+    // Trigger of suspend key while it is held, its key down was intercepted and so is the rest.
+    held_suspend_trigger: Option<Key>,
+    suspended: bool,
+    // Keys and clicks are not intercepted while focused window matches.
+    input_disabled_in: Option<fn(Window) -> bool>,
 }
 
 impl Cache {
@@ -226,35 +272,146 @@ impl Cache {
         *cache.monitor_layouts.get(&monitor).unwrap_or(&Layout::None)
     }
 
+    pub(crate) fn set_layout_deferred(&self, monitor: Monitor, deferred: bool) {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        if deferred {
+            cache.deferred_layouts.insert(monitor);
+        } else {
+            cache.deferred_layouts.remove(&monitor);
+        }
+    }
+
+    pub(crate) fn deferred_layouts(&self) -> std::vec::Vec<Monitor> {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &*self.inner.get() };
+        cache.deferred_layouts.iter().copied().collect()
+    }
+
+    pub(crate) fn set_master_ratio(&self, monitor: Monitor, ratio: f64) {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        cache.master_ratios.insert(monitor, ratio);
+    }
+
+    pub(crate) fn master_ratio(&self, monitor: Monitor) -> f64 {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &*self.inner.get() };
+        *cache.master_ratios.get(&monitor).unwrap_or(&0.5)
+    }
+
+    pub(crate) fn add_window_rule(&self, rule: WindowRule) {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        cache.window_rules.push(rule);
+    }
+
+    pub(crate) fn window_kind(&self, window: Window) -> WindowKind {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &*self.inner.get() };
+        winwin_common::classify_window(&window.descriptor(), &cache.window_rules)
+    }
+
     pub(crate) fn update_window_queue(&self, monitor: Monitor, window: Window) {
         // SAFETY: See safety section for `save_layout`.
         let cache = unsafe { &mut *self.inner.get() };
         let queues = &mut cache.window_queues;
 
+        // Queues hold stack order which focus must not disturb, only focus history is reordered.
         // There are three cases:
         // 1. Window was not present in any queue and must be added.
         // 2. Window was in different queue and must be moved.
-        // 3. Windows was in correct queue but must be moved to the front.
-        // Looping unconditionaly saves us from figuring out which case we were in. We simply try
-        // to remove from every queue than add it to correct slot.
-        for (_, q) in queues.iter_mut() {
-            q.retain(|w| *w != window);
-        }
+        // 3. Windows was in correct queue and keeps its slot.
         let target_queue_idx = queues
             .iter()
             .position(|(m, _)| *m == monitor)
             .expect("monitor must have its queue");
-        let queue = &mut queues[target_queue_idx].1;
-        queue.push_front(window);
+        if !queues[target_queue_idx].1.contains(&window) {
+            for (_, q) in queues.iter_mut() {
+                q.retain(|w| *w != window);
+            }
+            queues[target_queue_idx].1.push_front(window);
+        }
+
+        if cache.focus_history.front() != Some(&window) {
+            cache.focused_process = None;
+        }
+        cache.focus_history.retain(|w| *w != window);
+        cache.focus_history.push_front(window);
 
         // We update monitor ordering because moved windows must have been focused.
         // Removing and insterting seems to be cheap for VecDeque.
+        let queues = &mut cache.window_queues;
         let queue = queues
             .remove(target_queue_idx)
             .expect("monitor must have its queue");
         queues.push_front(queue);
     }
 
+    pub(crate) fn swap_windows(&self, w1: Window, w2: Window) {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        let queues = &mut cache.window_queues;
+
+        let find = |window: Window| {
+            queues
+                .iter()
+                .enumerate()
+                .find_map(|(qi, (_, q))| q.iter().position(|w| *w == window).map(|wi| (qi, wi)))
+        };
+        let (Some((q1, i1)), Some((q2, i2))) = (find(w1), find(w2)) else {
+            return;
+        };
+
+        queues[q1].1[i1] = w2;
+        queues[q2].1[i2] = w1;
+    }
+
+    pub(crate) fn move_window_in_queue(&self, window: Window, index: usize) {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        let Some(queue) = cache
+            .window_queues
+            .iter_mut()
+            .map(|(_, q)| q)
+            .find(|q| q.contains(&window))
+        else {
+            return;
+        };
+
+        queue.retain(|w| *w != window);
+        queue.insert(index.min(queue.len()), window);
+    }
+
+    pub(crate) fn move_window_to_monitor(&self, window: Window, monitor: Monitor) {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        let queues = &mut cache.window_queues;
+        let Some(target_queue_idx) = queues.iter().position(|(m, _)| *m == monitor) else {
+            return;
+        };
+
+        for (_, q) in queues.iter_mut() {
+            q.retain(|w| *w != window);
+        }
+        queues[target_queue_idx].1.push_back(window);
+    }
+
+    // Positive `n` rotates towards the front of the queue.
+    pub(crate) fn rotate_queue(&self, monitor: Monitor, n: isize) {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        let Some((_, queue)) = cache.window_queues.iter_mut().find(|(m, _)| *m == monitor) else {
+            return;
+        };
+
+        if queue.is_empty() {
+            return;
+        }
+        let n = n.rem_euclid(queue.len() as isize) as usize;
+        queue.rotate_left(n);
+    }
+
     pub(crate) fn add_window_queue(&self, monitor: Monitor) {
         // SAFETY: See safety section for `save_layout`.
         let cache = unsafe { &mut *self.inner.get() };
@@ -270,7 +427,10 @@ impl Cache {
             .find(|(m, _)| *m == monitor)
             .expect("monitor must have its queue")
             .1;
-        queue.push_front(window);
+        // Window might have been picked up by `fill` already.
+        if !queue.contains(&window) {
+            queue.push_front(window);
+        }
     }
 
     pub(crate) fn remove_window_from_queue(&self, window: Window, monitor: Monitor) {
@@ -283,30 +443,313 @@ impl Cache {
             .expect("monitor must have its queue")
             .1;
         queue.retain(|w| *w != window);
+        cache.focus_history.retain(|w| *w != window);
+        if cache
+            .focused_process
+            .as_ref()
+            .is_some_and(|(w, _)| *w == window)
+        {
+            cache.focused_process = None;
+        }
+        cache.minimized.remove(&window);
+        cache.floating.remove(&window);
+        cache.fullscreen.remove(&window);
+    }
+
+    pub(crate) fn set_floating(&self, window: Window, floating: bool) {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        if floating {
+            cache.floating.insert(window);
+        } else {
+            cache.floating.remove(&window);
+        }
+    }
+
+    pub(crate) fn is_floating(&self, window: Window) -> bool {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &*self.inner.get() };
+        cache.floating.contains(&window)
+    }
+
+    pub(crate) fn set_minimized(&self, window: Window, minimized: bool) {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        if minimized {
+            cache.minimized.insert(window);
+        } else {
+            cache.minimized.remove(&window);
+        }
+    }
+
+    pub(crate) fn set_fullscreen(
+        &self,
+        window: Window,
+        placement: WINDOWPLACEMENT,
+        style: WINDOW_STYLE,
+    ) {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        cache.fullscreen.insert(window, (placement, style));
+    }
+
+    pub(crate) fn take_fullscreen(
+        &self,
+        window: Window,
+    ) -> Option<(WINDOWPLACEMENT, WINDOW_STYLE)> {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        cache.fullscreen.remove(&window)
+    }
+
+    pub(crate) fn is_fullscreen(&self, window: Window) -> bool {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &*self.inner.get() };
+        cache.fullscreen.contains_key(&window)
+    }
+
+    pub(crate) fn set_focus_follows_mouse(&self, ffm: Option<FocusFollowsMouse>) {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        cache.focus_follows_mouse = ffm;
+    }
+
+    pub(crate) fn focus_follows_mouse(&self) -> Option<FocusFollowsMouse> {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &*self.inner.get() };
+        cache.focus_follows_mouse
+    }
+
+    pub(crate) fn set_cursor_warp(&self, warp: CursorWarp) {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        cache.cursor_warp = warp;
+    }
+
+    pub(crate) fn cursor_warp(&self) -> CursorWarp {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &*self.inner.get() };
+        cache.cursor_warp
+    }
+
+    pub(crate) fn set_navigation(&self, navigation: Navigation) {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        cache.navigation = navigation;
+    }
+
+    pub(crate) fn navigation(&self) -> Navigation {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &*self.inner.get() };
+        cache.navigation
+    }
+
+    pub(crate) fn set_key_mode(&self, mode: &'static str) {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        cache.key_map.mode = mode;
+    }
+
+    pub(crate) fn key_mode(&self) -> &'static str {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &*self.inner.get() };
+        cache.key_map.mode
+    }
+
+    pub(crate) fn set_modifier_matching(&self, matching: ModifierMatching) {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        cache.key_map.matching = matching;
+    }
+
+    pub(crate) fn modifier_matching(&self) -> ModifierMatching {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &*self.inner.get() };
+        cache.key_map.matching
+    }
+
+    pub(crate) fn set_keyboard_timeout(&self, timeout: KeyboardTimeout) {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        cache.keyboard_timeout = timeout;
+    }
+
+    pub(crate) fn keyboard_timeout(&self) -> KeyboardTimeout {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &*self.inner.get() };
+        cache.keyboard_timeout
+    }
+
+    pub(crate) fn set_tap_options(&self, options: TapOptions) {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        cache.taps.options = options;
+    }
+
+    pub(crate) fn set_mouse_drag(&self, mouse_drag: Option<MouseDrag>) {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        cache.mouse_drag = mouse_drag;
+    }
+
+    pub(crate) fn mouse_drag(&self) -> Option<MouseDrag> {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &*self.inner.get() };
+        cache.mouse_drag
+    }
+
+    pub(crate) fn set_suspend_key(&self, combo: Option<KeyCombo>) {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        cache.suspend_key = combo;
+    }
+
+    pub(crate) fn suspend_key(&self) -> Option<KeyCombo> {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &*self.inner.get() };
+        cache.suspend_key.clone()
+    }
+
+    pub(crate) fn toggle_suspended(&self) -> bool {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        cache.suspended = !cache.suspended;
+        cache.suspended
+    }
+
+    pub(crate) fn is_suspended(&self) -> bool {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &*self.inner.get() };
+        cache.suspended
+    }
+
+    pub(crate) fn set_input_disabled_in(&self, predicate: Option<fn(Window) -> bool>) {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        cache.input_disabled_in = predicate;
+    }
+
+    /// Whether keys and clicks should be let through untouched, either because winwin is
+    /// suspended or because focused window matches `set_input_disabled_in` predicate.
+    pub(crate) fn is_input_disabled(&self) -> bool {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &*self.inner.get() };
+        cache.suspended
+            || cache
+                .input_disabled_in
+                .is_some_and(|disabled_in| disabled_in(self.focused_window()))
+    }
+
+    pub(crate) fn set_held_suspend_trigger(&self, key: Option<Key>) {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        cache.held_suspend_trigger = key;
+    }
+
+    pub(crate) fn held_suspend_trigger(&self) -> Option<Key> {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &*self.inner.get() };
+        cache.held_suspend_trigger
+    }
+
+    pub(crate) fn pressed_keys(&self) -> std::vec::Vec<Key> {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &*self.inner.get() };
+        cache.key_map.pressed_keys()
+    }
+
+    pub(crate) fn add_chord(&self, steps: &[&[Key]]) -> ChordId {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        cache.chords.add(steps)
+    }
+
+    pub(crate) fn set_chord_options(&self, timeout: Duration, policy: AbandonPolicy) {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        cache.chords.set_options(timeout, policy);
+    }
+
+    pub(crate) fn feed_chords(&self, kb_delta: KBDelta, now: Instant) -> ChordOutcome {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        let pressed = cache.key_map.pressed_keys();
+        cache.chords.feed(kb_delta, &pressed, now)
+    }
+
+    pub(crate) fn tick_chords(&self, now: Instant) {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        cache.chords.tick(now);
+    }
+
+    pub(crate) fn chord_deadline(&self) -> Option<Instant> {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &*self.inner.get() };
+        cache.chords.deadline()
+    }
+
+    pub(crate) fn take_chord_replay(&self) -> std::vec::Vec<KBDelta> {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        cache.chords.take_replay()
+    }
+
+    pub(crate) fn is_minimized(&self, window: Window) -> bool {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &*self.inner.get() };
+        cache.minimized.contains(&window)
     }
 
     pub(crate) fn update_input<A>(
         &self,
         ctx: &Context<A>,
         kb_delta: KBDelta,
-        command_tx: SyncSender<KeyboardOp>,
+        seq: u64,
+        command_tx: SyncSender<KeyboardReply>,
     ) -> Input<A>
     where
         A: Allocator + Copy,
     {
         // SAFETY: See safety section for `save_layout`.
         let cache = unsafe { &mut *self.inner.get() };
-        cache.key_map.update(kb_delta);
-        let input = cache.key_map.input(ctx, command_tx);
+        let kind = cache.key_map.update(kb_delta);
+        let others_held = cache.key_map.pressed_count() > 1;
+        let tap = cache.taps.update(kb_delta, kind, others_held);
+        let key = Key::from_vk_code(kb_delta.vk_code);
+        let input = cache.key_map.input(ctx, key, kind, tap, seq, command_tx);
         input
     }
 
+    pub(crate) fn mouse_input<A>(
+        &self,
+        ctx: &Context<A>,
+        action: MouseAction,
+        point: Point,
+        seq: u64,
+        command_tx: SyncSender<KeyboardReply>,
+    ) -> MouseInput<A>
+    where
+        A: Allocator + Copy,
+    {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &*self.inner.get() };
+        cache
+            .key_map
+            .mouse_input(ctx, action, point, seq, command_tx)
+    }
+
     pub(crate) fn fill<A>(&self, ctx: &Context<A>)
     where
         A: Allocator + Copy,
     {
         let monitors = get_monitors_live(ctx);
         let windows = get_windows_live(ctx);
+        let minimized = windows
+            .iter()
+            .copied()
+            .filter(|w| is_minimised(*w))
+            .collect();
         let mut window_queues = VecDeque::new();
         for monitor in monitors {
             let queue = windows
@@ -321,6 +764,14 @@ impl Cache {
         // SAFETY: See safety section for `save_layout`.
         let cache = unsafe { &mut *self.inner.get() };
         cache.window_queues = window_queues;
+        cache.minimized = minimized;
+
+        cache.focus_history.clear();
+        cache.focused_process = None;
+        let focused = get_focused_window_live();
+        if windows.contains(&focused) {
+            cache.focus_history.push_front(focused);
+        }
     }
 
     pub(crate) fn monitor_with_window(&self, window: Window) -> Monitor {
@@ -354,6 +805,12 @@ impl Cache {
             .collect_with(ctx.alloc)
     }
 
+    pub(crate) fn contains_window(&self, window: Window) -> bool {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &*self.inner.get() };
+        cache.window_queues.iter().any(|(_, q)| q.contains(&window))
+    }
+
     pub(crate) fn monitors<A>(&self, ctx: &Context<A>) -> Vec<Monitor, A>
     where
         A: Allocator + Copy,
@@ -396,6 +853,11 @@ impl Cache {
     pub(crate) fn focused_window(&self) -> Window {
         // SAFETY: See safety section for `save_layout`.
         let cache = unsafe { &*self.inner.get() };
+        if let Some(window) = cache.focus_history.front() {
+            return *window;
+        }
+
+        // Nothing was focused since cache was filled.
         *cache
             .window_queues
             .front()
@@ -404,83 +866,698 @@ impl Cache {
             .front()
             .unwrap_or(&Window::default())
     }
+
+    pub(crate) fn process_name(&self, window: Window) -> String {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &mut *self.inner.get() };
+        if cache.focus_history.front() != Some(&window) {
+            return window.process_name();
+        }
+
+        match &cache.focused_process {
+            Some((w, name)) if *w == window => name.clone(),
+            _ => {
+                let name = window.process_name();
+                cache.focused_process = Some((window, name.clone()));
+                name
+            }
+        }
+    }
+
+    pub(crate) fn focus_history<A>(&self, ctx: &Context<A>) -> Vec<Window, A>
+    where
+        A: Allocator + Copy,
+    {
+        // SAFETY: See safety section for `save_layout`.
+        let cache = unsafe { &*self.inner.get() };
+        cache.focus_history.iter().copied().collect_with(ctx.alloc)
+    }
+}
+
+/// Mode active at startup and the one to return to when leaving other modes.
+pub const DEFAULT_KEY_MODE: &str = "default";
+
+/// Switches binding mode reported by `Input::mode`. Modes are plain names, keys are only
+/// intercepted when a binding checked in the current mode matches, so whatever is not bound in a
+/// mode reaches apps as usual.
+pub fn set_key_mode<A>(ctx: &Context<A>, mode: &'static str)
+where
+    A: Allocator + Copy,
+{
+    ctx.cache.set_key_mode(mode);
+}
+
+pub fn key_mode<A>(ctx: &Context<A>) -> &'static str
+where
+    A: Allocator + Copy,
+{
+    ctx.cache.key_mode()
+}
+
+/// Sets combo that suspends winwin until it is pressed again, e.g. for games and remote desktop
+/// sessions. While suspended no keys or clicks are intercepted and window events are not
+/// reported, so nothing is tiled. Changes are reported with `Event::SuspendChanged`.
+pub fn set_suspend_key<A>(ctx: &Context<A>, combo: Option<KeyCombo>)
+where
+    A: Allocator + Copy,
+{
+    ctx.cache.set_suspend_key(combo);
+}
+
+/// Whether winwin is suspended with the key set by `set_suspend_key`.
+pub fn is_suspended<A>(ctx: &Context<A>) -> bool
+where
+    A: Allocator + Copy,
+{
+    ctx.cache.is_suspended()
+}
+
+/// Lets all keys and clicks through while focused window matches `predicate`, e.g. for
+/// fullscreen games. Bindings, chords, taps and Alt+drag are inactive there, window events are
+/// still reported. Suspend key keeps working.
+pub fn set_input_disabled_in<A>(ctx: &Context<A>, predicate: Option<fn(Window) -> bool>)
+where
+    A: Allocator + Copy,
+{
+    ctx.cache.set_input_disabled_in(predicate);
+}
+
+/// Sets whether side specific modifiers in bindings match only their side. By default either
+/// side matches.
+pub fn set_modifier_matching<A>(ctx: &Context<A>, matching: ModifierMatching)
+where
+    A: Allocator + Copy,
+{
+    ctx.cache.set_modifier_matching(matching);
+}
+
+pub fn modifier_matching<A>(ctx: &Context<A>) -> ModifierMatching
+where
+    A: Allocator + Copy,
+{
+    ctx.cache.modifier_matching()
 }
 
-#[derive(Default)]
 pub struct KeyMap {
     keys: [u32; 8],
+    mode: &'static str,
+    matching: ModifierMatching,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        Self {
+            keys: [0; 8],
+            mode: DEFAULT_KEY_MODE,
+            matching: ModifierMatching::default(),
+        }
+    }
 }
 
 impl KeyMap {
-    pub fn update(&mut self, kb_delta: KBDelta) {
+    pub fn pressed_count(&self) -> u32 {
+        self.keys.iter().map(|k| k.count_ones()).sum()
+    }
+
+    pub fn pressed_keys(&self) -> std::vec::Vec<Key> {
+        self.pressed_iter().collect()
+    }
+
+    fn pressed_iter(&self) -> impl Iterator<Item = Key> + '_ {
+        (0..256usize)
+            .filter(|i| self.keys[i / 32] & (1 << (i % 32)) != 0)
+            .map(|i| Key::from_vk_code(i as u8))
+    }
+
+    /// Applies `kb_delta`, key down of a key that is already held is an auto-repeat.
+    pub fn update(&mut self, kb_delta: KBDelta) -> KeyEventKind {
         let idx = (kb_delta.vk_code / 32) as usize;
         let bit = kb_delta.vk_code % 32;
         match kb_delta.key_state {
             KeyState::Up => {
                 self.keys[idx] &= !(1 << bit);
+                KeyEventKind::Up
             }
+            KeyState::Down if self.keys[idx] & (1 << bit) != 0 => KeyEventKind::Repeat,
             KeyState::Down => {
                 self.keys[idx] |= 1 << bit;
+                KeyEventKind::Down
             }
         }
     }
 
-    pub fn input<'a, A>(&self, ctx: &'a Context<A>, tx: SyncSender<KeyboardOp>) -> Input<A>
+    pub fn input<'a, A>(
+        &self,
+        ctx: &'a Context<A>,
+        key: Key,
+        kind: KeyEventKind,
+        tap: Option<Tap>,
+        seq: u64,
+        tx: SyncSender<KeyboardReply>,
+    ) -> Input<A>
     where
         A: Allocator + Copy,
     {
         let mut pressed_keys = Vec::new_in(ctx.alloc);
+        pressed_keys.extend(self.pressed_iter());
 
-        for i in 0..256 {
-            let idx = i / 32;
-            let bit = i % 32;
-            if self.keys[idx] & (1 << bit) != 0 {
-                pressed_keys.push(Key::from_vk_code(i as u8));
-            }
+        Input {
+            keys: pressed_keys,
+            key,
+            kind,
+            tap,
+            mode: self.mode,
+            matching: self.matching,
+            seq,
+            intercept_tx: tx,
         }
+    }
 
-        Input {
+    pub fn mouse_input<'a, A>(
+        &self,
+        ctx: &'a Context<A>,
+        action: MouseAction,
+        point: Point,
+        seq: u64,
+        tx: SyncSender<KeyboardReply>,
+    ) -> MouseInput<A>
+    where
+        A: Allocator + Copy,
+    {
+        let mut pressed_keys = Vec::new_in(ctx.alloc);
+        pressed_keys.extend(self.pressed_iter());
+
+        MouseInput {
             keys: pressed_keys,
+            action,
+            point,
+            mode: self.mode,
+            matching: self.matching,
+            seq,
             intercept_tx: tx,
         }
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Tap {
+    Single,
+    /// Second tap within `TapOptions::double_tap_interval`, the first one was reported as single.
+    Double,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct TapOptions {
+    /// Longest time key can be held to still count as tap.
+    pub max_hold: Duration,
+    /// Longest time between release of the first tap and press of the second one.
+    pub double_tap_interval: Duration,
+}
+
+impl Default for TapOptions {
+    fn default() -> Self {
+        Self {
+            max_hold: Duration::from_millis(200),
+            double_tap_interval: Duration::from_millis(300),
+        }
+    }
+}
+
+/// Sets timings used to tell taps from holds.
+pub fn set_tap_options<A>(ctx: &Context<A>, options: TapOptions)
+where
+    A: Allocator + Copy,
+{
+    ctx.cache.set_tap_options(options);
+}
+
+/// Tells quick taps of a key from holding it, e.g. as a modifier. A key is tapped when it is
+/// released shortly after being pressed and no other key was pressed in between. Works on
+/// `KBDelta` timestamps alone.
+#[derive(Default)]
+pub struct TapDetector {
+    options: TapOptions,
+    // Key that is held and could still be tapped, with time it was pressed.
+    candidate: Option<(u8, u32)>,
+    // Last tapped key with time it was released.
+    last_tap: Option<(u8, u32)>,
+}
+
+impl TapDetector {
+    pub fn new(options: TapOptions) -> Self {
+        Self {
+            options,
+            ..Default::default()
+        }
+    }
+
+    /// `kind` is what `KeyMap::update` reported for `kb_delta`, `others_held` tells whether keys
+    /// other than this one are held.
+    pub fn update(
+        &mut self,
+        kb_delta: KBDelta,
+        kind: KeyEventKind,
+        others_held: bool,
+    ) -> Option<Tap> {
+        let vk_code = kb_delta.vk_code;
+        match kind {
+            KeyEventKind::Repeat => None,
+            KeyEventKind::Down => {
+                // Any other key going down turns the held key into a modifier.
+                self.candidate = (!others_held).then_some((vk_code, kb_delta.time));
+                if self.last_tap.is_some_and(|(last, _)| last != vk_code) {
+                    self.last_tap = None;
+                }
+                None
+            }
+            KeyEventKind::Up => {
+                let (candidate, pressed_at) = self.candidate.take()?;
+                let held = kb_delta.time.wrapping_sub(pressed_at);
+                if candidate != vk_code || held > self.options.max_hold.as_millis() as u32 {
+                    return None;
+                }
+
+                let is_double = self.last_tap.is_some_and(|(last, released_at)| {
+                    last == vk_code
+                        && pressed_at.wrapping_sub(released_at)
+                            <= self.options.double_tap_interval.as_millis() as u32
+                });
+
+                if is_double {
+                    self.last_tap = None;
+                    Some(Tap::Double)
+                } else {
+                    self.last_tap = Some((vk_code, kb_delta.time));
+                    Some(Tap::Single)
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum KeyEventKind {
+    Down,
+    Up,
+    /// Key down generated by holding a key.
+    Repeat,
+}
+
 #[derive(Debug)]
 pub struct Input<A: Allocator> {
     keys: Vec<Key, A>,
-    intercept_tx: SyncSender<KeyboardOp>,
+    // Key that changed state, it is not in `keys` if it was released.
+    key: Key,
+    kind: KeyEventKind,
+    tap: Option<Tap>,
+    mode: &'static str,
+    matching: ModifierMatching,
+    // Keystroke this input was created for, replies to other keystrokes are ignored.
+    seq: u64,
+    intercept_tx: SyncSender<KeyboardReply>,
 }
 
 impl<A: Allocator> Drop for Input<A> {
     fn drop(&mut self) {
-        let _ = self.intercept_tx.try_send(KeyboardOp::DoNothing);
+        self.reply(KeyboardOp::DoNothing);
     }
 }
 
 impl<A: Allocator> Input<A> {
+    pub(crate) fn intercept(&self) {
+        self.reply(KeyboardOp::InterceptKeypress);
+    }
+
+    /// Intercepts release of a tapped key and sends it again preceded by an unassigned key.
+    /// Windows opens start menu when Win is released alone and focuses menu bar on Alt, this way
+    /// it never sees them released alone.
+    pub(crate) fn intercept_masked(&self) {
+        self.intercept();
+        send_keystrokes(&[
+            Keystroke::Key(MASK_VK_CODE, KeyState::Down),
+            Keystroke::Key(MASK_VK_CODE, KeyState::Up),
+            Keystroke::Key(self.key.to_vk_code(), KeyState::Up),
+        ]);
+    }
+
+    // Only the first reply counts, the rest is discarded by the hook.
+    fn reply(&self, op: KeyboardOp) {
+        let reply = KeyboardReply { seq: self.seq, op };
+        let _ = self.intercept_tx.try_send(reply);
+    }
+
+    /// Key that was pressed, released or repeated.
+    pub fn key(&self) -> Key {
+        self.key
+    }
+
+    pub fn kind(&self) -> KeyEventKind {
+        self.kind
+    }
+
+    /// Set on release of a key that was tapped, see `set_tap_options`.
+    pub fn tap(&self) -> Option<Tap> {
+        self.tap
+    }
+
+    /// Binding mode that was active when keys were pressed.
+    pub fn mode(&self) -> &'static str {
+        self.mode
+    }
+
+    pub fn in_mode(&self, mode: &str) -> bool {
+        self.mode == mode
+    }
+
     pub fn pressed(&self, key: Key) -> bool {
         let pressed = self.pressed_no_intercept(key);
         if pressed {
-            let _ = self.intercept_tx.try_send(KeyboardOp::InterceptKeypress);
+            self.intercept();
         }
         pressed
     }
 
     pub fn pressed_no_intercept(&self, key: Key) -> bool {
-        self.keys[0] == key
+        KeyCombo::new(&[], key).matches(&self.keys, self.matching)
     }
 
     pub fn all_pressed(&self, keys: &[Key]) -> bool {
         let pressed = self.all_pressed_no_intercept(keys);
         if pressed {
-            let _ = self.intercept_tx.try_send(KeyboardOp::InterceptKeypress);
+            self.intercept();
         }
         pressed
     }
 
+    /// Checks whether `keys` are held, in any order. Held modifiers have to be exactly the ones
+    /// in `keys`, other held keys are ignored. See `set_modifier_matching`.
     pub fn all_pressed_no_intercept(&self, keys: &[Key]) -> bool {
-        // Make sure len is the same otherwise we might match different keybind.
-        keys.iter().all(|it| self.keys.iter().any(|k| *k == *it)) && self.keys.len() == keys.len()
+        KeyCombo::from_keys(keys).is_some_and(|combo| combo.matches(&self.keys, self.matching))
+    }
+
+    pub fn completes(&self, keys: &[Key]) -> bool {
+        let completes = self.completes_no_intercept(keys);
+        if completes {
+            self.intercept();
+        }
+        completes
+    }
+
+    /// Same as `all_pressed_no_intercept` but also requires the key that changed state to be the
+    /// one completing `keys`, so pressing a modifier while the rest is held does nothing.
+    pub fn completes_no_intercept(&self, keys: &[Key]) -> bool {
+        KeyCombo::from_keys(keys)
+            .is_some_and(|combo| combo.triggered_by(self.key, &self.keys, self.matching))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MouseAction {
+    Button(MouseButton, KeyState),
+    /// Wheel rotation, see `WheelDelta::delta`.
+    Wheel(i16),
+    HorizontalWheel(i16),
+}
+
+/// Mouse button or wheel event together with keys held at the time. Like `Input`, it lets event
+/// through once dropped unless it was intercepted.
+#[derive(Debug)]
+pub struct MouseInput<A: Allocator> {
+    keys: Vec<Key, A>,
+    action: MouseAction,
+    point: Point,
+    mode: &'static str,
+    matching: ModifierMatching,
+    seq: u64,
+    intercept_tx: SyncSender<KeyboardReply>,
+}
+
+impl<A: Allocator> Drop for MouseInput<A> {
+    fn drop(&mut self) {
+        self.reply(KeyboardOp::DoNothing);
+    }
+}
+
+impl<A: Allocator> MouseInput<A> {
+    /// Keeps event from reaching apps. Intercepting button press alone leaves apps with a release
+    /// they never saw pressed, so release should usually be intercepted too.
+    pub fn intercept(&self) {
+        self.reply(KeyboardOp::InterceptKeypress);
+    }
+
+    // Only the first reply counts, the rest is discarded by the hook.
+    fn reply(&self, op: KeyboardOp) {
+        let reply = KeyboardReply { seq: self.seq, op };
+        let _ = self.intercept_tx.try_send(reply);
+    }
+
+    pub fn action(&self) -> MouseAction {
+        self.action
+    }
+
+    /// Cursor position at the time of the event.
+    pub fn point(&self) -> Point {
+        self.point
+    }
+
+    /// Binding mode that was active when event happened.
+    pub fn mode(&self) -> &'static str {
+        self.mode
+    }
+
+    pub fn in_mode(&self, mode: &str) -> bool {
+        self.mode == mode
+    }
+
+    pub fn modifiers_pressed(&self, modifiers: &[Key]) -> bool {
+        let pressed = self.modifiers_pressed_no_intercept(modifiers);
+        if pressed {
+            self.intercept();
+        }
+        pressed
+    }
+
+    /// Checks whether held modifiers are exactly `modifiers`, e.g. `&[Key::Menu]` for Alt+click.
+    /// See `set_modifier_matching`.
+    pub fn modifiers_pressed_no_intercept(&self, modifiers: &[Key]) -> bool {
+        modifiers_match(modifiers, &self.keys, self.matching)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIN: Key = Key::Win;
+    const J: Key = Key::J;
+
+    /// Keys held down on a simulated keyboard, shared by suites feeding hook events.
+    #[derive(Default)]
+    pub(crate) struct HeldKeys(Vec<Key>);
+
+    impl HeldKeys {
+        /// Returns `Repeat` if `key` was held already.
+        pub(crate) fn down(&mut self, key: Key) -> KeyEventKind {
+            if self.0.contains(&key) {
+                return KeyEventKind::Repeat;
+            }
+            self.0.push(key);
+            KeyEventKind::Down
+        }
+
+        pub(crate) fn up(&mut self, key: Key) {
+            self.0.retain(|k| *k != key);
+        }
+
+        pub(crate) fn any_other_than(&self, key: Key) -> bool {
+            self.0.iter().any(|k| *k != key)
+        }
+
+        pub(crate) fn as_slice(&self) -> &[Key] {
+            &self.0
+        }
+    }
+
+    struct Keyboard {
+        taps: TapDetector,
+        held: HeldKeys,
+        time: u32,
+    }
+
+    impl Keyboard {
+        fn new() -> Self {
+            Self::starting_at(1000)
+        }
+
+        fn starting_at(time: u32) -> Self {
+            Self {
+                taps: TapDetector::new(TapOptions::default()),
+                held: HeldKeys::default(),
+                time,
+            }
+        }
+
+        fn down(&mut self, key: Key) -> Option<Tap> {
+            let kind = self.held.down(key);
+            self.update(key, KeyState::Down, kind)
+        }
+
+        fn up(&mut self, key: Key) -> Option<Tap> {
+            self.held.up(key);
+            self.update(key, KeyState::Up, KeyEventKind::Up)
+        }
+
+        fn tap(&mut self, key: Key, held_ms: u32) -> Option<Tap> {
+            assert_eq!(self.down(key), None);
+            self.wait(held_ms);
+            self.up(key)
+        }
+
+        fn wait(&mut self, ms: u32) {
+            self.time = self.time.wrapping_add(ms);
+        }
+
+        fn update(&mut self, key: Key, key_state: KeyState, kind: KeyEventKind) -> Option<Tap> {
+            let kb_delta = KBDelta {
+                vk_code: key.to_vk_code(),
+                key_state,
+                time: self.time,
+            };
+            let others_held = self.held.any_other_than(key);
+            self.taps.update(kb_delta, kind, others_held)
+        }
+    }
+
+    #[test]
+    fn single_tap() {
+        let mut kb = Keyboard::new();
+        assert_eq!(kb.tap(WIN, 50), Some(Tap::Single));
+    }
+
+    #[test]
+    fn hold_is_not_a_tap() {
+        let mut kb = Keyboard::new();
+        assert_eq!(kb.tap(WIN, 200), Some(Tap::Single));
+        kb.wait(1000);
+        assert_eq!(kb.tap(WIN, 201), None);
+    }
+
+    #[test]
+    fn repeats_do_not_restart_hold() {
+        let mut kb = Keyboard::new();
+        kb.down(WIN);
+        kb.wait(150);
+        assert_eq!(kb.down(WIN), None);
+        kb.wait(100);
+        assert_eq!(kb.up(WIN), None);
+    }
+
+    #[test]
+    fn double_tap_within_interval() {
+        let mut kb = Keyboard::new();
+        assert_eq!(kb.tap(WIN, 50), Some(Tap::Single));
+        kb.wait(300);
+        assert_eq!(kb.tap(WIN, 50), Some(Tap::Double));
+    }
+
+    #[test]
+    fn double_tap_outside_interval() {
+        let mut kb = Keyboard::new();
+        assert_eq!(kb.tap(WIN, 50), Some(Tap::Single));
+        kb.wait(301);
+        assert_eq!(kb.tap(WIN, 50), Some(Tap::Single));
+    }
+
+    #[test]
+    fn third_tap_starts_over() {
+        let mut kb = Keyboard::new();
+        assert_eq!(kb.tap(WIN, 50), Some(Tap::Single));
+        kb.wait(100);
+        assert_eq!(kb.tap(WIN, 50), Some(Tap::Double));
+        kb.wait(100);
+        assert_eq!(kb.tap(WIN, 50), Some(Tap::Single));
+    }
+
+    #[test]
+    fn taps_of_different_keys_are_not_double() {
+        let mut kb = Keyboard::new();
+        assert_eq!(kb.tap(WIN, 50), Some(Tap::Single));
+        kb.wait(100);
+        assert_eq!(kb.tap(J, 50), Some(Tap::Single));
+        kb.wait(100);
+        assert_eq!(kb.tap(WIN, 50), Some(Tap::Single));
+    }
+
+    #[test]
+    fn tap_cancelled_by_other_key() {
+        let mut kb = Keyboard::new();
+        kb.down(WIN);
+        assert_eq!(kb.tap(J, 20), None);
+        assert_eq!(kb.up(WIN), None);
+    }
+
+    #[test]
+    fn tap_cancelled_by_other_key_released_later() {
+        let mut kb = Keyboard::new();
+        kb.down(WIN);
+        kb.down(J);
+        assert_eq!(kb.up(WIN), None);
+        assert_eq!(kb.up(J), None);
+    }
+
+    #[test]
+    fn key_pressed_while_other_is_held_is_not_tapped() {
+        let mut kb = Keyboard::new();
+        kb.down(J);
+        assert_eq!(kb.tap(WIN, 50), None);
+    }
+
+    #[test]
+    fn cancelled_tap_does_not_count_towards_double() {
+        let mut kb = Keyboard::new();
+        assert_eq!(kb.tap(WIN, 50), Some(Tap::Single));
+        kb.wait(50);
+        kb.down(WIN);
+        assert_eq!(kb.tap(J, 20), None);
+        assert_eq!(kb.up(WIN), None);
+        kb.wait(50);
+        assert_eq!(kb.tap(WIN, 50), Some(Tap::Single));
+    }
+
+    #[test]
+    fn timestamps_wrap_around() {
+        let mut kb = Keyboard::starting_at(u32::MAX - 20);
+        assert_eq!(kb.tap(WIN, 50), Some(Tap::Single));
+        kb.wait(100);
+        assert_eq!(kb.tap(WIN, 50), Some(Tap::Double));
+    }
+
+    #[test]
+    fn input_is_disabled_while_suspended_or_in_matching_window() {
+        let cache = Cache::default();
+        cache.add_window_queue(Monitor::default());
+        assert!(!cache.is_input_disabled());
+
+        cache.set_input_disabled_in(Some(|window| window == Window::default()));
+        assert!(cache.is_input_disabled());
+        cache.set_input_disabled_in(Some(|window| window != Window::default()));
+        assert!(!cache.is_input_disabled());
+
+        cache.toggle_suspended();
+        assert!(cache.is_input_disabled());
+    }
+
+    #[test]
+    fn layout_is_deferred_once_per_monitor() {
+        let cache = Cache::default();
+        let monitor = Monitor::default();
+        assert!(cache.deferred_layouts().is_empty());
+
+        cache.set_layout_deferred(monitor, true);
+        cache.set_layout_deferred(monitor, true);
+        assert_eq!(cache.deferred_layouts(), [monitor]);
+
+        cache.set_layout_deferred(monitor, false);
+        assert!(cache.deferred_layouts().is_empty());
     }
 }
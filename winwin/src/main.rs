@@ -1,3 +1,5 @@
+use std::cell::Cell;
+use std::time::Duration;
 use tracing::Level;
 use winwin::*;
 
@@ -17,108 +19,326 @@ fn main() {
     let mod_key = Key::AltLeft;
     let ctx = Context::new();
 
-    // SAFETY: There can be only one `EventQueue` at a time.
-    let mut queue = unsafe { EventQueue::new(&ctx) };
-    loop {
-        let event = queue.next_event(&ctx);
-        match event {
-            Event::KeyPress(input) => {
-                if input.all_pressed(&[mod_key, Key::X]) {
-                    queue.shutdown();
-                    break;
-                }
+    // Focus follows mouse is opt-in, uncomment to focus tiled windows by hovering over them.
+    // set_focus_follows_mouse(
+    //     &ctx,
+    //     Some(FocusFollowsMouse {
+    //         delay: Duration::from_millis(150),
+    //         ignore: None,
+    //     }),
+    // );
 
-                // Focus switching.
-                if input.all_pressed(&[mod_key, Key::ShiftLeft, Key::J]) {
-                    focus_next_window(&ctx);
-                }
+    set_cursor_warp(&ctx, CursorWarp::OnMonitorChange);
 
-                if input.all_pressed(&[mod_key, Key::ShiftLeft, Key::K]) {
-                    focus_prev_window(&ctx);
-                }
+    set_mouse_drag(
+        &ctx,
+        Some(MouseDrag {
+            modifier: Key::Menu,
+            tiled: TiledDrag::Swap,
+        }),
+    );
 
-                // 2d window navigation.
-                if input.all_pressed(&[mod_key, Key::L]) {
-                    move_focus(&ctx, Direction::Right);
-                }
+    // Everything is off until pressed again, e.g. in games.
+    set_suspend_key(
+        &ctx,
+        Some("Ctrl+Alt+ScrollLock".parse().expect("combo is valid")),
+    );
+    // Games get every key.
+    set_input_disabled_in(&ctx, Some(|window| window.class_name() == "UnrealWindow"));
 
-                if input.all_pressed(&[mod_key, Key::H]) {
-                    move_focus(&ctx, Direction::Left);
-                }
+    set_navigation(
+        &ctx,
+        Navigation {
+            wrap: false,
+            cross_monitors: true,
+        },
+    );
 
-                if input.all_pressed(&[mod_key, Key::J]) {
-                    move_focus(&ctx, Direction::Down);
-                }
+    // Layout selection with mod+Space followed by a letter.
+    set_chord_options(&ctx, Duration::from_secs(1), AbandonPolicy::Replay);
+    let stack_chord = add_chord(&ctx, &[&[mod_key, Key::Space], &[Key::S]]);
+    let grid_chord = add_chord(&ctx, &[&[mod_key, Key::Space], &[Key::G]]);
+    let full_chord = add_chord(&ctx, &[&[mod_key, Key::Space], &[Key::F]]);
 
-                if input.all_pressed(&[mod_key, Key::K]) {
-                    move_focus(&ctx, Direction::Up);
-                }
+    let quit = Cell::new(false);
+    let mut bindings = Bindings::new();
+    bindings.bind(&[mod_key, Key::X], "quit", |_| quit.set(true));
 
-                // Swap adjacent windows.
-                if input.all_pressed(&[mod_key, Key::CtrlLeft, Key::L]) {
-                    let window = get_focused_window(&ctx);
-                    swap_adjacent(&ctx, window, Direction::Right);
-                }
+    // Focus switching.
+    bindings
+        .bind(
+            &[mod_key, Key::ShiftLeft, Key::J],
+            "focus next window",
+            focus_next_window,
+        )
+        .bind(
+            &[mod_key, Key::ShiftLeft, Key::K],
+            "focus previous window",
+            focus_prev_window,
+        )
+        .bind(&[mod_key, Key::Tab], "focus last window", focus_last);
 
-                if input.all_pressed(&[mod_key, Key::CtrlLeft, Key::H]) {
-                    let window = get_focused_window(&ctx);
-                    swap_adjacent(&ctx, window, Direction::Left);
-                }
+    bindings.bind_double_tap(mod_key, "focus last window", focus_last);
 
-                if input.all_pressed(&[mod_key, Key::CtrlLeft, Key::J]) {
-                    let window = get_focused_window(&ctx);
-                    swap_adjacent(&ctx, window, Direction::Down);
-                }
+    // 2d window navigation.
+    bindings
+        .bind(&[mod_key, Key::L], "focus right", |ctx| {
+            move_focus(ctx, Direction::Right)
+        })
+        .bind(&[mod_key, Key::H], "focus left", |ctx| {
+            move_focus(ctx, Direction::Left)
+        })
+        .bind(&[mod_key, Key::J], "focus down", |ctx| {
+            move_focus(ctx, Direction::Down)
+        })
+        .bind(&[mod_key, Key::K], "focus up", |ctx| {
+            move_focus(ctx, Direction::Up)
+        });
 
-                if input.all_pressed(&[mod_key, Key::CtrlLeft, Key::K]) {
-                    let window = get_focused_window(&ctx);
-                    swap_adjacent(&ctx, window, Direction::Up);
-                }
+    // Swap adjacent windows.
+    let directions = [
+        (Key::L, Direction::Right, "swap right"),
+        (Key::H, Direction::Left, "swap left"),
+        (Key::J, Direction::Down, "swap down"),
+        (Key::K, Direction::Up, "swap up"),
+    ];
+    for (key, direction, description) in directions {
+        bindings.bind(&[mod_key, Key::CtrlLeft, key], description, move |ctx| {
+            let window = get_focused_window(ctx);
+            swap_adjacent(ctx, window, direction);
+        });
+    }
 
-                // Apply selected layout.
-                if input.all_pressed(&[mod_key, Key::Q]) {
-                    let monitor = get_focused_monitor(&ctx);
-                    apply_layout(&ctx, monitor, Layout::Stack);
-                }
+    // Stack manipulation.
+    bindings
+        .bind(&[mod_key, Key::Return], "swap with master", |ctx| {
+            let window = get_focused_window(ctx);
+            swap_with_master(ctx, window);
+        })
+        .bind(&[mod_key, Key::ShiftLeft, Key::Return], "promote", |ctx| {
+            let window = get_focused_window(ctx);
+            promote(ctx, window);
+        })
+        .bind(&[mod_key, Key::Up], "rotate stack up", |ctx| {
+            let monitor = get_focused_monitor(ctx);
+            rotate_stack_up(ctx, monitor);
+        })
+        .repeat()
+        .bind(&[mod_key, Key::Down], "rotate stack down", |ctx| {
+            let monitor = get_focused_monitor(ctx);
+            rotate_stack_down(ctx, monitor);
+        })
+        .repeat();
 
-                if input.all_pressed(&[mod_key, Key::W]) {
-                    let monitor = get_focused_monitor(&ctx);
-                    apply_layout(&ctx, monitor, Layout::Full);
-                }
+    // Swap with adjacent window or send to adjacent monitor.
+    bindings
+        .bind(
+            &[mod_key, Key::ShiftLeft, Key::L],
+            "swap or send right",
+            |ctx| {
+                let window = get_focused_window(ctx);
+                swap_or_send(ctx, window, Direction::Right);
+            },
+        )
+        .bind(
+            &[mod_key, Key::ShiftLeft, Key::H],
+            "swap or send left",
+            |ctx| {
+                let window = get_focused_window(ctx);
+                swap_or_send(ctx, window, Direction::Left);
+            },
+        );
 
-                if input.all_pressed(&[mod_key, Key::E]) {
-                    let monitor = get_focused_monitor(&ctx);
-                    apply_layout(&ctx, monitor, Layout::Grid);
-                }
+    // Move mode, bare keys move focused window around until Esc, everything else passes through.
+    bindings.bind(&[mod_key, Key::S], "enter move mode", |ctx| {
+        set_key_mode(ctx, "move")
+    });
+    bindings.bind_in("move", &[Key::Esc], "leave move mode", |ctx| {
+        set_key_mode(ctx, DEFAULT_KEY_MODE)
+    });
+    let directions = [
+        (Key::L, Direction::Right, "move right"),
+        (Key::H, Direction::Left, "move left"),
+        (Key::J, Direction::Down, "move down"),
+        (Key::K, Direction::Up, "move up"),
+    ];
+    for (key, direction, description) in directions {
+        bindings.bind_in("move", &[key], description, move |ctx| {
+            let window = get_focused_window(ctx);
+            swap_or_send(ctx, window, direction);
+        });
+    }
 
-                if input.all_pressed(&[mod_key, Key::R]) {
-                    let monitor = get_focused_monitor(&ctx);
-                    apply_layout(&ctx, monitor, Layout::None);
+    // Resize mode, bare H/L shrink and grow master column of stack layout until Esc. Floating
+    // windows are resized instead, tiled ones stay in layout.
+    bindings.bind(
+        &[mod_key, Key::ShiftLeft, Key::R],
+        "enter resize mode",
+        |ctx| set_key_mode(ctx, "resize"),
+    );
+    bindings.bind_in("resize", &[Key::Esc], "leave resize mode", |ctx| {
+        set_key_mode(ctx, DEFAULT_KEY_MODE)
+    });
+    let steps = [
+        (Key::L, 50, 0, 0.05, "grow width"),
+        (Key::H, -50, 0, -0.05, "shrink width"),
+        (Key::J, 0, 50, 0.0, "grow height"),
+        (Key::K, 0, -50, 0.0, "shrink height"),
+    ];
+    for (key, dx, dy, master_delta, description) in steps {
+        bindings
+            .bind_in("resize", &[key], description, move |ctx| {
+                let window = get_focused_window(ctx);
+                if !is_floating(ctx, window) {
+                    if master_delta != 0.0 {
+                        resize_master(ctx, get_focused_monitor(ctx), master_delta);
+                    }
+                    return;
                 }
+                let start = Point { x: 0, y: 0 };
+                let rect = drag_rect(
+                    DragKind::Resize,
+                    window.rect(),
+                    start,
+                    Point { x: dx, y: dy },
+                );
+                window.set_rect(rect);
+            })
+            .repeat();
+    }
+
+    // Apply selected layout.
+    let layouts = [
+        (Key::Q, Layout::Stack, "stack layout"),
+        (Key::W, Layout::Full, "full layout"),
+        (Key::E, Layout::Grid, "grid layout"),
+        (Key::R, Layout::None, "no layout"),
+    ];
+    for (key, layout, description) in layouts {
+        bindings.bind(&[mod_key, key], description, move |ctx| {
+            let monitor = get_focused_monitor(ctx);
+            apply_layout(ctx, monitor, layout);
+        });
+    }
 
-                // Moving windows across monitors.
-                if input.all_pressed(&[mod_key, Key::Right]) {
-                    let window = get_focused_window(&ctx);
-                    let monitors = get_monitors(&ctx);
+    // Moving windows across monitors.
+    bindings.bind(&[mod_key, Key::Right], "send to third monitor", |ctx| {
+        let window = get_focused_window(ctx);
+        let monitors = get_monitors(ctx);
 
-                    dbg!(window.title());
-                    send(&ctx, window, monitors[2]);
-                }
+        dbg!(window.title());
+        send(ctx, window, monitors[2]);
+    });
 
-                // Swap windows on monitors.
-                if input.all_pressed(&[mod_key, Key::P]) {
-                    let monitors = get_monitors(&ctx);
-                    swap_monitors(&ctx, monitors[0], monitors[2]);
-                }
+    // Swap windows on monitors.
+    bindings.bind(&[mod_key, Key::P], "swap first and third monitor", |ctx| {
+        let monitors = get_monitors(ctx);
+        swap_monitors(ctx, monitors[0], monitors[2]);
+    });
 
-                // Window closing.
-                if input.all_pressed(&[mod_key, Key::BackSlash]) {
-                    let window = get_focused_window(&ctx);
-                    kill_window(window);
-                }
+    // Fullscreen.
+    bindings.bind(&[mod_key, Key::F], "toggle fullscreen", |ctx| {
+        let window = get_focused_window(ctx);
+        toggle_fullscreen(ctx, window);
+    });
 
-                if input.all_pressed(&[mod_key, Key::CtrlLeft, Key::BackSlash]) {
-                    kill_all_windows(&ctx);
+    // Minimizing and restoring.
+    bindings
+        .bind(&[mod_key, Key::M], "minimize others", |ctx| {
+            let window = get_focused_window(ctx);
+            minimize_others(ctx, window);
+        })
+        .bind(&[mod_key, Key::ShiftLeft, Key::M], "restore all", |ctx| {
+            let monitor = get_focused_monitor(ctx);
+            restore_all(ctx, monitor);
+        });
+
+    // Window closing.
+    bindings
+        .bind(&[mod_key, Key::BackSlash], "close window", |ctx| {
+            let window = get_focused_window(ctx);
+            kill_window(window);
+        })
+        .bind(
+            &[mod_key, Key::CtrlLeft, Key::BackSlash],
+            "close all windows",
+            kill_all_windows,
+        );
+
+    // Remapped keys and snippets, sent to focused window.
+    bindings
+        .bind(&[mod_key, Key::U], "undo", |ctx| {
+            send_combo(ctx, &"Ctrl+Z".parse().expect("combo is valid"))
+        })
+        .except_in(|window| get_process_name(&ctx, window).eq_ignore_ascii_case("code.exe"))
+        .bind(&[mod_key, Key::ShiftLeft, Key::D], "type shrug", |ctx| {
+            send_text(ctx, "¯\\_(ツ)_/¯")
+        });
+
+    for binding in bindings.iter() {
+        let combo = KeyCombo::from_keys(&binding.keys).expect("bindings have keys");
+        tracing::debug!("{}: {} {}", binding.mode, combo, binding.description);
+    }
+
+    for conflict in bindings.conflicts(modifier_matching(&ctx)) {
+        tracing::warn!(
+            "{} in {} mode: \"{}\" is shadowed by \"{}\"",
+            KeyCombo::from_keys(&conflict.keys).expect("bindings have keys"),
+            conflict.mode,
+            conflict.shadowed,
+            conflict.shadowed_by
+        );
+    }
+
+    // SAFETY: There can be only one `EventQueue` at a time.
+    let mut queue = unsafe { EventQueue::new(&ctx) };
+    loop {
+        let event = queue.next_event(&ctx);
+        match event {
+            Event::KeyPress(input) | Event::KeyRepeat(input) | Event::KeyRelease(input) => {
+                bindings.dispatch(&ctx, &input);
+                if quit.get() {
+                    queue.shutdown();
+                    break;
+                }
+            }
+            Event::MouseButton(input) => {
+                // Close window under cursor with mod+middle click, release is swallowed as well.
+                if let MouseAction::Button(MouseButton::Middle, state) = input.action() {
+                    if input.modifiers_pressed(&[mod_key]) && matches!(state, KeyState::Down) {
+                        kill_window(get_window_at(input.point()));
+                    }
+                }
+            }
+            Event::MouseWheel(input) => {
+                // Cycle layouts with mod+wheel.
+                if let MouseAction::Wheel(delta) = input.action() {
+                    if input.modifiers_pressed(&[mod_key]) {
+                        let monitor = get_focused_monitor(&ctx);
+                        let layout = next_layout(layout_on(&ctx, monitor), delta < 0);
+                        apply_layout(&ctx, monitor, layout);
+                    }
+                }
+            }
+            Event::MouseMove(_) => {}
+            Event::SuspendChanged(suspended) => {
+                tracing::info!(suspended);
+                // Windows opened or closed in the meantime were not tiled.
+                if !suspended {
+                    for monitor in get_monitors(&ctx) {
+                        let layout = layout_on(&ctx, monitor);
+                        apply_layout(&ctx, monitor, layout);
+                    }
+                }
+            }
+            Event::Chord(id) => {
+                let monitor = get_focused_monitor(&ctx);
+                if id == stack_chord {
+                    apply_layout(&ctx, monitor, Layout::Stack);
+                } else if id == grid_chord {
+                    apply_layout(&ctx, monitor, Layout::Grid);
+                } else if id == full_chord {
+                    apply_layout(&ctx, monitor, Layout::Full);
                 }
             }
             Event::WindowOpen(window, monitor) => {
@@ -134,7 +354,22 @@ fn main() {
                 // `monitor` value is valid and designates last monitor the window was on.
                 let layout = layout_on(&ctx, monitor);
                 apply_layout(&ctx, monitor, layout);
+            }
+            Event::WindowMinimized(_, monitor) | Event::WindowRestored(_, monitor) => {
+                // Minimized windows keep their slot, restoring puts them back where they were.
+                let layout = layout_on(&ctx, monitor);
+                apply_layout(&ctx, monitor, layout);
+            }
+            Event::WindowTitleChanged(window) => {
+                tracing::debug!(title = window.title());
             } // TODO: Handle monitor connection/disconection.
         }
     }
 }
+
+fn next_layout(layout: Layout, forward: bool) -> Layout {
+    const LAYOUTS: [Layout; 4] = [Layout::None, Layout::Stack, Layout::Grid, Layout::Full];
+    let idx = LAYOUTS.iter().position(|l| *l == layout).unwrap_or(0);
+    let step = if forward { 1 } else { LAYOUTS.len() - 1 };
+    LAYOUTS[(idx + step) % LAYOUTS.len()]
+}
@@ -2,13 +2,15 @@ use allocator_api2::alloc::Allocator;
 use allocator_api2::vec::*;
 use std::hash::{Hash, Hasher};
 use std::mem::MaybeUninit;
+use std::time::Duration;
 use windows::Win32::UI::HiDpi::*;
 use windows::Win32::UI::WindowsAndMessaging::*;
 use windows::{Win32::Foundation::*, Win32::Graphics::Gdi::*, Win32::System::Threading::*};
-use winwin_common::Rect;
+use winwin_common::{Point, Rect, WindowDescriptor, WindowKind, WindowRule};
 
 use crate::{trace_result, trace_result_b, Arena, Context, IteratorCollectWithAlloc};
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Direction {
     Left,
     Right,
@@ -37,6 +39,12 @@ impl From<usize> for Window {
     }
 }
 
+impl Hash for Window {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.handle.0.hash(state);
+    }
+}
+
 impl Window {
     pub fn rect(&self) -> Rect {
         let mut rect = RECT::default();
@@ -91,6 +99,21 @@ impl Window {
         };
     }
 
+    pub fn placement(&self) -> WINDOWPLACEMENT {
+        let mut placement = WINDOWPLACEMENT {
+            length: std::mem::size_of::<WINDOWPLACEMENT>() as u32,
+            ..Default::default()
+        };
+        let res = unsafe { GetWindowPlacement(self.handle, &mut placement) };
+        trace_result!(res);
+        placement
+    }
+
+    pub fn set_placement(&self, placement: &WINDOWPLACEMENT) {
+        let res = unsafe { SetWindowPlacement(self.handle, placement) };
+        trace_result!(res);
+    }
+
     pub fn is_on_monitor(&self, monitor: Monitor) -> bool {
         let target_handle = unsafe { MonitorFromWindow(self.handle, MONITOR_DEFAULTTONULL) };
         return target_handle == monitor.handle;
@@ -116,6 +139,10 @@ impl Window {
         todo!()
     }
 
+    pub fn descriptor(&self) -> WindowDescriptor {
+        WindowDescriptor::query(self.handle)
+    }
+
     pub fn style(&self) -> WINDOW_STYLE {
         let style = unsafe { GetWindowLongW(self.handle, GWL_STYLE) };
         WINDOW_STYLE(style as _)
@@ -126,6 +153,58 @@ impl Window {
         WINDOW_EX_STYLE(style as _)
     }
 
+    pub fn set_style(&self, style: WINDOW_STYLE) {
+        unsafe {
+            let _ = SetWindowLongW(self.handle, GWL_STYLE, style.0 as _);
+
+            // Frame changes are not picked up until window is told about them.
+            let res = SetWindowPos(
+                self.handle,
+                HWND::default(),
+                0,
+                0,
+                0,
+                0,
+                SWP_FRAMECHANGED | SWP_NOMOVE | SWP_NOSIZE | SWP_NOZORDER | SWP_NOACTIVATE,
+            );
+            trace_result!(res);
+        }
+    }
+
+    pub fn class_name(&self) -> String {
+        let mut buff = [0; 256];
+        let end = unsafe { GetClassNameW(self.handle, &mut buff) }.max(0);
+        String::from_utf16_lossy(&buff[..end as usize])
+    }
+
+    /// File name of executable that owns this window, e.g. `firefox.exe`. Empty if process can't
+    /// be queried, e.g. when it runs elevated and we do not.
+    pub fn process_name(&self) -> String {
+        unsafe {
+            let mut pid = 0;
+            GetWindowThreadProcessId(self.handle, Some(&mut pid));
+            let Ok(process) = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, pid) else {
+                return String::new();
+            };
+
+            let mut buff = [0; 260];
+            let mut len = buff.len() as u32;
+            let res = QueryFullProcessImageNameW(
+                process,
+                PROCESS_NAME_WIN32,
+                windows::core::PWSTR(buff.as_mut_ptr()),
+                &mut len,
+            );
+            let _ = CloseHandle(process);
+            if res.is_err() {
+                return String::new();
+            }
+
+            let path = String::from_utf16_lossy(&buff[..len as usize]);
+            path.rsplit('\\').next().unwrap_or_default().to_string()
+        }
+    }
+
     pub fn focus(&self) {
         unsafe {
             let current_thread_id = GetCurrentThreadId();
@@ -187,6 +266,18 @@ impl Window {
         trace_result!(res);
     }
 
+    pub fn restore(&self) {
+        let res = unsafe {
+            PostMessageA(
+                self.handle,
+                WM_SYSCOMMAND,
+                WPARAM(SC_RESTORE as _),
+                LPARAM(0),
+            )
+        };
+        trace_result!(res);
+    }
+
     pub fn is_invalid(&self) -> bool {
         self.handle.is_invalid()
     }
@@ -219,7 +310,17 @@ impl From<usize> for Monitor {
 }
 
 impl Monitor {
+    /// Work area of this monitor, that is monitor area without the taskbar.
     pub fn rect(&self) -> Rect {
+        self.info().rcWork.into()
+    }
+
+    /// Entire monitor area.
+    pub fn full_rect(&self) -> Rect {
+        self.info().rcMonitor.into()
+    }
+
+    fn info(&self) -> MONITORINFO {
         let mut info = MONITORINFO {
             cbSize: core::mem::size_of::<MONITORINFO>() as u32,
             ..Default::default()
@@ -227,7 +328,7 @@ impl Monitor {
 
         let success = unsafe { GetMonitorInfoW(self.handle, &mut info) };
         trace_result_b!(success);
-        info.rcWork.into()
+        info
     }
 }
 
@@ -261,7 +362,7 @@ fn get_dpi_for_monitor(monitor: Monitor) -> (u32, u32) {
     (dpi_x, dpi_y)
 }
 
-#[derive(Default, Clone, Copy, Debug)]
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Layout {
     #[default]
     None,
@@ -275,6 +376,12 @@ where
     A: Allocator + Copy,
 {
     save_layout(ctx, monitor, layout);
+    let suspended = is_layout_suspended(ctx, monitor);
+    ctx.cache.set_layout_deferred(monitor, suspended);
+    if suspended {
+        return;
+    }
+
     match layout {
         Layout::None => {}
         Layout::Stack => set_stack_layout(ctx, monitor),
@@ -283,6 +390,86 @@ where
     }
 }
 
+/// Applies saved layouts that were skipped while a fullscreen window covered their monitor. Games
+/// leave exclusive fullscreen without telling us, so this runs whenever focus changes.
+pub(crate) fn apply_deferred_layouts<A>(ctx: &Context<A>)
+where
+    A: Allocator + Copy,
+{
+    for monitor in ctx.cache.deferred_layouts() {
+        apply_layout(ctx, monitor, layout_on(ctx, monitor));
+    }
+}
+
+/// Layout is suspended while monitor is covered by a fullscreen window. This is either a window
+/// toggled with `toggle_fullscreen` or an app that went exclusive fullscreen on its own.
+pub fn is_layout_suspended<A>(ctx: &Context<A>, monitor: Monitor) -> bool
+where
+    A: Allocator + Copy,
+{
+    let windows = get_windows_on_monitor(ctx, monitor);
+    if windows.iter().any(|w| ctx.cache.is_fullscreen(*w)) {
+        return true;
+    }
+
+    // Games are usually borderless popups which we do not manage, check foreground window too.
+    let foreground = get_focused_window_live();
+    let foreground_monitor = get_monitor_with_window_live(ctx, foreground);
+    foreground_monitor == monitor && is_exclusive_fullscreen(foreground, monitor)
+}
+
+fn is_exclusive_fullscreen(window: Window, monitor: Monitor) -> bool {
+    if window.is_invalid() || is_minimised(window) || window.style().contains(WS_CAPTION) {
+        return false;
+    }
+
+    // Desktop covers entire monitor too.
+    let class_name = window.class_name();
+    if class_name == "Progman" || class_name == "WorkerW" {
+        return false;
+    }
+
+    window.rect() == monitor.full_rect()
+}
+
+/// Makes `window` cover entire monitor, including taskbar, and strips its caption. Layout on that
+/// monitor is suspended until the window is toggled back to its previous rect and style.
+/// Windows that went fullscreen on their own are left alone.
+pub fn toggle_fullscreen<A>(ctx: &Context<A>, window: Window)
+where
+    A: Allocator + Copy,
+{
+    let monitor = get_monitor_with_window(ctx, window);
+
+    if let Some((placement, style)) = ctx.cache.take_fullscreen(window) {
+        window.set_style(style);
+        window.set_placement(&placement);
+
+        let layout = layout_on(ctx, monitor);
+        apply_layout(ctx, monitor, layout);
+        return;
+    }
+
+    if window.is_invalid() || is_exclusive_fullscreen(window, monitor) {
+        return;
+    }
+
+    // Placement remembers whether window was maximized and where it goes once restored.
+    let placement = window.placement();
+    if placement.showCmd == SW_SHOWMAXIMIZED.0 as _ {
+        // `set_rect` restores maximized windows asynchronously, restore would land after sizing
+        // and undo it.
+        unsafe {
+            let _ = ShowWindow(window.handle, SW_RESTORE);
+        }
+    }
+
+    let style = window.style();
+    ctx.cache.set_fullscreen(window, placement, style);
+    window.set_style(style & !(WS_CAPTION | WS_THICKFRAME));
+    window.set_rect(monitor.full_rect());
+}
+
 pub fn save_layout<A>(ctx: &Context<A>, monitor: Monitor, layout: Layout)
 where
     A: Allocator + Copy,
@@ -297,6 +484,25 @@ where
     ctx.cache.layout_on(monitor)
 }
 
+/// Share of monitor width taken by master window in stack layout, half by default.
+pub fn master_ratio<A>(ctx: &Context<A>, monitor: Monitor) -> f64
+where
+    A: Allocator + Copy,
+{
+    ctx.cache.master_ratio(monitor)
+}
+
+/// Grows master window of stack layout on `monitor` by `delta` share of monitor width, shrinks it
+/// if negative, and applies saved layout again. Both columns keep at least a tenth of the width.
+pub fn resize_master<A>(ctx: &Context<A>, monitor: Monitor, delta: f64)
+where
+    A: Allocator + Copy,
+{
+    let ratio = (ctx.cache.master_ratio(monitor) + delta).clamp(0.1, 0.9);
+    ctx.cache.set_master_ratio(monitor, ratio);
+    apply_layout(ctx, monitor, layout_on(ctx, monitor));
+}
+
 pub fn get_monitor_with_window<A>(ctx: &Context<A>, window: Window) -> Monitor
 where
     A: Allocator + Copy,
@@ -320,6 +526,74 @@ where
     ctx.cache.windows_on_monitor(ctx, monitor)
 }
 
+/// Windows on `monitor` that take part in layout, in queue order. Unlike `get_windows_on_monitor`
+/// this skips minimized and floating windows.
+pub fn get_tiled_windows_on_monitor<A>(ctx: &Context<A>, monitor: Monitor) -> Vec<Window, A>
+where
+    A: Allocator + Copy,
+{
+    let mut windows = get_windows_on_monitor(ctx, monitor);
+    windows.retain(|w| !ctx.cache.is_minimized(*w) && !ctx.cache.is_floating(*w));
+    windows
+}
+
+/// Tiled windows are managed windows laid out on a monitor that has a layout, minimized, floating
+/// and fullscreen windows do not count.
+pub fn is_tiled<A>(ctx: &Context<A>, window: Window) -> bool
+where
+    A: Allocator + Copy,
+{
+    if !ctx.cache.contains_window(window)
+        || ctx.cache.is_minimized(window)
+        || ctx.cache.is_floating(window)
+        || ctx.cache.is_fullscreen(window)
+    {
+        return false;
+    }
+
+    let monitor = get_monitor_with_window(ctx, window);
+    !matches!(layout_on(ctx, monitor), Layout::None)
+}
+
+pub fn is_floating<A>(ctx: &Context<A>, window: Window) -> bool
+where
+    A: Allocator + Copy,
+{
+    ctx.cache.is_floating(window)
+}
+
+/// Floating windows stay where user puts them, layout of their monitor skips them.
+pub fn set_floating<A>(ctx: &Context<A>, window: Window, floating: bool)
+where
+    A: Allocator + Copy,
+{
+    if !ctx.cache.contains_window(window) || ctx.cache.is_floating(window) == floating {
+        return;
+    }
+
+    ctx.cache.set_floating(window, floating);
+    let monitor = get_monitor_with_window(ctx, window);
+    let layout = layout_on(ctx, monitor);
+    apply_layout(ctx, monitor, layout);
+}
+
+pub fn toggle_floating<A>(ctx: &Context<A>, window: Window)
+where
+    A: Allocator + Copy,
+{
+    set_floating(ctx, window, !ctx.cache.is_floating(window));
+}
+
+/// Top level window under `point`.
+pub fn get_window_at(point: Point) -> Window {
+    let pt = POINT {
+        x: point.x,
+        y: point.y,
+    };
+    let handle = unsafe { GetAncestor(WindowFromPoint(pt), GA_ROOT) };
+    Window { handle }
+}
+
 pub fn get_monitors<A>(ctx: &Context<A>) -> Vec<Monitor, A>
 where
     A: Allocator + Copy,
@@ -365,37 +639,21 @@ pub(crate) fn get_windows_live<A>(ctx: &Context<A>) -> Vec<Window, &Arena>
 where
     A: Allocator + Copy,
 {
-    extern "system" fn push_visible_window(window: HWND, lparam: LPARAM) -> BOOL {
+    extern "system" fn push_window(window: HWND, lparam: LPARAM) -> BOOL {
         unsafe {
-            let len = GetWindowTextLengthW(window);
-
-            let mut info = WINDOWINFO {
-                cbSize: core::mem::size_of::<WINDOWINFO>() as u32,
-                ..Default::default()
-            };
-
-            trace_result! {
-                GetWindowInfo(window, &mut info)
-            };
-
-            if len != 0 && info.dwStyle.contains(WS_VISIBLE) && !info.dwStyle.contains(WS_POPUP) {
-                let dest_vec = lparam.0 as *mut Vec<Window, &Arena>;
-                (*dest_vec).push(Window { handle: window });
-            }
-
+            let dest_vec = lparam.0 as *mut Vec<Window, &Arena>;
+            (*dest_vec).push(Window { handle: window });
             TRUE
         }
     }
 
     let mut windows = Vec::new_in(&ctx.arena);
-    let res = unsafe {
-        EnumWindows(
-            Some(push_visible_window),
-            LPARAM(&mut windows as *mut _ as isize),
-        )
-    };
+    let res = unsafe { EnumWindows(Some(push_window), LPARAM(&mut windows as *mut _ as isize)) };
     match res {
-        Ok(_) => windows,
+        Ok(_) => {
+            windows.retain(|w| ctx.cache.window_kind(*w) == WindowKind::Managed);
+            windows
+        }
         Err(e) => {
             tracing::error!(error = ?e);
             Vec::new_in(&ctx.arena)
@@ -403,6 +661,23 @@ where
     }
 }
 
+/// Registers rule that overrides default window classification. Rules are consulted in
+/// registration order, register them before creating `EventQueue` so they apply to windows that
+/// are already open.
+pub fn add_window_rule<A>(ctx: &Context<A>, rule: WindowRule)
+where
+    A: Allocator + Copy,
+{
+    ctx.cache.add_window_rule(rule);
+}
+
+pub fn window_kind<A>(ctx: &Context<A>, window: Window) -> WindowKind
+where
+    A: Allocator + Copy,
+{
+    ctx.cache.window_kind(window)
+}
+
 pub fn get_focused_monitor<A>(ctx: &Context<A>) -> Monitor
 where
     A: Allocator + Copy,
@@ -423,6 +698,15 @@ where
     ctx.cache.focused_window()
 }
 
+/// Same as `Window::process_name` but for focused window it is looked up only once per focus
+/// change, e.g. for binding scopes checked on every keystroke.
+pub fn get_process_name<A>(ctx: &Context<A>, window: Window) -> String
+where
+    A: Allocator + Copy,
+{
+    ctx.cache.process_name(window)
+}
+
 pub(crate) fn get_focused_window_live() -> Window {
     let hwnd = unsafe { GetForegroundWindow() };
     if hwnd.is_invalid() {
@@ -435,7 +719,7 @@ fn set_stack_layout<A>(ctx: &Context<A>, monitor: Monitor)
 where
     A: Allocator + Copy,
 {
-    let windows = get_windows_on_monitor(ctx, monitor);
+    let windows = get_tiled_windows_on_monitor(ctx, monitor);
 
     match windows.len() {
         0 => return,
@@ -456,6 +740,7 @@ where
             transform_rects_for_stack_uninit(
                 bounding_rect,
                 scale,
+                ctx.cache.master_ratio(monitor),
                 &windows_rect,
                 &windows_client_rect,
                 transformed_rects,
@@ -472,12 +757,14 @@ where
 pub fn transform_rects_for_stack_uninit(
     bounding_rect: Rect,
     scale: f64,
+    master_ratio: f64,
     windows_rect: &[Rect],
     windows_client_rect: &[Rect],
     transformed_rects: &mut [MaybeUninit<Rect>],
 ) {
     let partitions_needed = windows_rect.len() as i32 - 1;
-    let partition_width = (bounding_rect.width as f64 / 2.0 / scale).round() as i32;
+    let main_width = (bounding_rect.width as f64 * master_ratio / scale).round() as i32;
+    let partition_width = (bounding_rect.width as f64 / scale).round() as i32 - main_width;
     let partition_height =
         (bounding_rect.height as f64 / partitions_needed as f64 / scale).round() as i32;
 
@@ -485,7 +772,7 @@ pub fn transform_rects_for_stack_uninit(
         Rect {
             x: bounding_rect.x,
             y: bounding_rect.y,
-            width: partition_width,
+            width: main_width,
             height: bounding_rect.height,
         },
         windows_rect[0],
@@ -498,7 +785,7 @@ pub fn transform_rects_for_stack_uninit(
         let sub_window_idx = i - 1;
         let rect = adjust_for_non_client_area(
             Rect {
-                x: bounding_rect.x + partition_width,
+                x: bounding_rect.x + main_width,
                 y: bounding_rect.y + sub_window_idx as i32 * partition_height,
                 width: partition_width,
                 height: partition_height,
@@ -514,6 +801,7 @@ pub fn transform_rects_for_stack_uninit(
 pub fn transform_rects_for_stack(
     bounding_rect: Rect,
     scale: f64,
+    master_ratio: f64,
     windows_rect: &[Rect],
     windows_client_rect: &[Rect],
     transformed_rects: &mut [Rect],
@@ -521,6 +809,7 @@ pub fn transform_rects_for_stack(
     transform_rects_for_stack_uninit(
         bounding_rect,
         scale,
+        master_ratio,
         windows_rect,
         windows_client_rect,
         // SAFETY: MaybeUninit is repr transparent T.
@@ -532,7 +821,7 @@ fn set_grid_layout<A>(ctx: &Context<A>, monitor: Monitor)
 where
     A: Allocator + Copy,
 {
-    let windows = get_windows_on_monitor(ctx, monitor);
+    let windows = get_tiled_windows_on_monitor(ctx, monitor);
 
     match windows.len() {
         0 => return,
@@ -638,18 +927,141 @@ pub fn set_full_layout<A>(ctx: &Context<A>, monitor: Monitor)
 where
     A: Allocator + Copy,
 {
-    let windows = get_windows_on_monitor(ctx, monitor);
+    let windows = get_tiled_windows_on_monitor(ctx, monitor);
     for window in windows {
         window.maximize();
     }
 }
 
-pub fn focus_next_window(ctx: &Context) {
-    todo!()
+/// Focuses window that follows focused one in stack order of its monitor, wraps around.
+pub fn focus_next_window<A>(ctx: &Context<A>)
+where
+    A: Allocator + Copy,
+{
+    focus_in_stack(ctx, 1);
+}
+
+/// Focuses window that precedes focused one in stack order of its monitor, wraps around.
+pub fn focus_prev_window<A>(ctx: &Context<A>)
+where
+    A: Allocator + Copy,
+{
+    focus_in_stack(ctx, -1);
+}
+
+fn focus_in_stack<A>(ctx: &Context<A>, offset: isize)
+where
+    A: Allocator + Copy,
+{
+    let window = get_focused_window(ctx);
+    let monitor = get_focused_monitor(ctx);
+    let windows = get_tiled_windows_on_monitor(ctx, monitor);
+    if windows.is_empty() {
+        return;
+    }
+
+    let target_idx = match windows.iter().position(|w| *w == window) {
+        Some(idx) => (idx as isize + offset).rem_euclid(windows.len() as isize) as usize,
+        None => 0,
+    };
+    focus_with_warp(ctx, windows[target_idx]);
+}
+
+/// Toggles focus between two most recently focused windows.
+pub fn focus_last<A>(ctx: &Context<A>)
+where
+    A: Allocator + Copy,
+{
+    let history = get_focus_history(ctx);
+    if let Some(window) = history.get(1) {
+        focus_with_warp(ctx, *window);
+    }
+}
+
+/// Windows ordered from most to least recently focused, across all monitors.
+pub fn get_focus_history<A>(ctx: &Context<A>) -> Vec<Window, A>
+where
+    A: Allocator + Copy,
+{
+    ctx.cache.focus_history(ctx)
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct FocusFollowsMouse {
+    /// How long cursor has to stay over a window before it gets focused.
+    pub delay: Duration,
+    /// Windows matched by this predicate are never focused by hovering.
+    pub ignore: Option<fn(Window) -> bool>,
+}
+
+impl FocusFollowsMouse {
+    pub(crate) fn ignores(&self, window: Window) -> bool {
+        self.ignore.is_some_and(|ignore| ignore(window))
+    }
+}
+
+/// Enables focusing tiled windows by hovering over them, `None` turns it off. Off by default. Mouse
+/// hook tracks hovered windows only if this is enabled before `EventQueue` is created.
+pub fn set_focus_follows_mouse<A>(ctx: &Context<A>, ffm: Option<FocusFollowsMouse>)
+where
+    A: Allocator + Copy,
+{
+    ctx.cache.set_focus_follows_mouse(ffm);
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CursorWarp {
+    /// Cursor follows every focus change made with keyboard navigation.
+    Always,
+    /// Cursor follows focus only when it lands on another monitor.
+    OnMonitorChange,
+    #[default]
+    Never,
+}
+
+/// Sets when keyboard navigation should move cursor to the center of newly focused window.
+pub fn set_cursor_warp<A>(ctx: &Context<A>, warp: CursorWarp)
+where
+    A: Allocator + Copy,
+{
+    ctx.cache.set_cursor_warp(warp);
 }
 
-pub fn focus_prev_window(ctx: &Context) {
-    todo!()
+fn focus_with_warp<A>(ctx: &Context<A>, window: Window)
+where
+    A: Allocator + Copy,
+{
+    let origin_monitor = get_focused_monitor(ctx);
+    window.focus();
+    warp_cursor(ctx, window, origin_monitor);
+}
+
+fn warp_cursor<A>(ctx: &Context<A>, window: Window, origin_monitor: Monitor)
+where
+    A: Allocator + Copy,
+{
+    let should_warp = match ctx.cache.cursor_warp() {
+        CursorWarp::Always => true,
+        CursorWarp::OnMonitorChange => get_monitor_with_window(ctx, window) != origin_monitor,
+        CursorWarp::Never => false,
+    };
+    if !should_warp || window.is_invalid() {
+        return;
+    }
+
+    let rect = window.rect();
+    let mut cursor = POINT::default();
+    let res = unsafe { GetCursorPos(&mut cursor) };
+    trace_result!(res);
+
+    // Window was most likely focused with the mouse, it is already where it should be.
+    if rect.contains(Point::from(cursor)) {
+        return;
+    }
+
+    let center = rect.center();
+    let res = unsafe { SetCursorPos(center.x, center.y) };
+    trace_result!(res);
 }
 
 pub fn move_focus<A>(ctx: &Context<A>, direction: Direction)
@@ -657,9 +1069,9 @@ where
     A: Allocator + Copy,
 {
     let origin_window = get_focused_window(ctx);
-    let target_window = get_adjacent_window(ctx, origin_window, direction);
-
-    target_window.focus();
+    if let Some(target_window) = get_adjacent_window(ctx, origin_window, direction) {
+        focus_with_warp(ctx, target_window);
+    }
 }
 
 pub fn swap(w1: Window, w2: Window) {
@@ -674,60 +1086,184 @@ pub fn swap_adjacent<A>(ctx: &Context<A>, window: Window, direction: Direction)
 where
     A: Allocator + Copy,
 {
-    let other = get_adjacent_window(ctx, window, direction);
-    swap(window, other);
+    if let Some(other) = get_adjacent_window(ctx, window, direction) {
+        swap_tiles(ctx, window, other);
+    }
 }
 
-pub fn send<A>(ctx: &Context<A>, window: Window, monitor: Monitor)
+fn swap_tiles<A>(ctx: &Context<A>, window: Window, other: Window)
+where
+    A: Allocator + Copy,
+{
+    if !is_tiled(ctx, window) || !is_tiled(ctx, other) {
+        swap(window, other);
+        return;
+    }
+
+    // Swapping rects alone would be undone by the next layout, stack order has to change.
+    let other_monitor = get_monitor_with_window(ctx, other);
+    ctx.cache.swap_windows(window, other);
+    let monitor = get_monitor_with_window(ctx, window);
+    let layout = layout_on(ctx, monitor);
+    apply_layout(ctx, monitor, layout);
+
+    // Navigation may cross monitors.
+    if other_monitor != monitor {
+        let layout = layout_on(ctx, other_monitor);
+        apply_layout(ctx, other_monitor, layout);
+    }
+}
+
+/// Swaps `window` with the master window, which is the first tiled window in monitor's stack.
+pub fn swap_with_master<A>(ctx: &Context<A>, window: Window)
 where
     A: Allocator + Copy,
 {
+    let monitor = get_monitor_with_window(ctx, window);
+    let windows = get_tiled_windows_on_monitor(ctx, monitor);
+    let Some(master) = windows.first().copied() else {
+        return;
+    };
+
+    if master != window && windows.contains(&window) {
+        ctx.cache.swap_windows(window, master);
+        let layout = layout_on(ctx, monitor);
+        apply_layout(ctx, monitor, layout);
+    }
+}
+
+/// Makes `window` the master window, windows that were above it move one slot down.
+pub fn promote<A>(ctx: &Context<A>, window: Window)
+where
+    A: Allocator + Copy,
+{
+    move_to_index(ctx, window, 0);
+}
+
+/// Moves `window` to `index` in stack order of its monitor, counting only tiled windows. Index past
+/// the end moves window to the bottom of the stack.
+pub fn move_to_index<A>(ctx: &Context<A>, window: Window, index: usize)
+where
+    A: Allocator + Copy,
+{
+    let monitor = get_monitor_with_window(ctx, window);
+    let tiled = get_tiled_windows_on_monitor(ctx, monitor);
+    if tiled.is_empty() || !tiled.contains(&window) {
+        return;
+    }
+
+    // Minimized windows are still in the queue, translate index into queue position.
+    let target = tiled[index.min(tiled.len() - 1)];
+    if target == window {
+        return;
+    }
+    let windows = get_windows_on_monitor(ctx, monitor);
+    let queue_index = windows
+        .iter()
+        .position(|w| *w == target)
+        .expect("tiled window is in the queue");
+
+    ctx.cache.move_window_in_queue(window, queue_index);
+    let layout = layout_on(ctx, monitor);
+    apply_layout(ctx, monitor, layout);
+}
+
+/// Every window moves one slot up the stack, master goes to the bottom.
+pub fn rotate_stack_up<A>(ctx: &Context<A>, monitor: Monitor)
+where
+    A: Allocator + Copy,
+{
+    ctx.cache.rotate_queue(monitor, 1);
     let layout = layout_on(ctx, monitor);
+    apply_layout(ctx, monitor, layout);
+}
+
+/// Every window moves one slot down the stack, bottom window becomes master.
+pub fn rotate_stack_down<A>(ctx: &Context<A>, monitor: Monitor)
+where
+    A: Allocator + Copy,
+{
+    ctx.cache.rotate_queue(monitor, -1);
+    let layout = layout_on(ctx, monitor);
+    apply_layout(ctx, monitor, layout);
+}
+
+/// Handles the end of user drag. Window dropped over another tile swaps places with it, window
+/// dropped anywhere else snaps back into its slot. `slot` is the rect window had before the drag.
+pub(crate) fn drop_window<A>(ctx: &Context<A>, window: Window, slot: Rect)
+where
+    A: Allocator + Copy,
+{
     let origin_monitor = get_monitor_with_window(ctx, window);
-    let mut windows = get_windows_on_monitor(ctx, monitor);
-    windows.push(window);
+    if matches!(layout_on(ctx, origin_monitor), Layout::None) {
+        // Nothing is tiled here, user is free to place the window wherever they want.
+        return;
+    }
 
-    let windows_rect: Vec<Rect, &Arena> = windows.iter().map(|w| w.rect()).collect_with(&ctx.arena);
-    let windows_client_rect: Vec<Rect, &Arena> = windows
-        .iter()
-        .map(|w| w.client_rect())
-        .collect_with(&ctx.arena);
-    let transformed_rects = ctx.arena.slice_mut_uninit::<Rect>(windows.len());
-    let bounding_rect = monitor.rect();
-    let (dpi_x, _) = get_dpi_for_monitor(monitor);
-    let scale = dpi_x as f64 / 96.0;
+    let dropped_rect = window.rect();
+    let was_resized = dropped_rect.width != slot.width || dropped_rect.height != slot.height;
 
-    match layout {
-        Layout::None => {
-            for (out, cpy) in transformed_rects.iter_mut().zip(windows_rect.iter()) {
-                out.write(*cpy);
+    // Window has to cover other tile more than its own slot for the drop to count.
+    let mut best_overlap = dropped_rect.intersection(&slot).area();
+    let mut target = None;
+    if !was_resized {
+        for monitor in get_monitors(ctx) {
+            if matches!(layout_on(ctx, monitor), Layout::None) {
+                continue;
+            }
+
+            for other in get_tiled_windows_on_monitor(ctx, monitor) {
+                if other == window {
+                    continue;
+                }
+
+                let overlap = dropped_rect.intersection(&other.rect()).area();
+                if overlap > best_overlap {
+                    best_overlap = overlap;
+                    target = Some((other, monitor));
+                }
             }
         }
-        Layout::Stack => {
-            transform_rects_for_stack_uninit(
-                bounding_rect,
-                scale,
-                &windows_rect,
-                &windows_client_rect,
-                transformed_rects,
-            );
-        }
-        Layout::Full => {
-            todo!()
-        }
-        Layout::Grid => {
-            todo!()
+    }
+
+    if let Some((other, target_monitor)) = target {
+        ctx.cache.swap_windows(window, other);
+        if target_monitor != origin_monitor {
+            let layout = layout_on(ctx, target_monitor);
+            apply_layout(ctx, target_monitor, layout);
         }
     }
 
-    for (window, rect) in windows.iter().zip(transformed_rects.iter()) {
-        // SAFETY: Slice was initialized by `transform_rects` function.
-        window.set_rect(unsafe { rect.assume_init() });
+    let layout = layout_on(ctx, origin_monitor);
+    apply_layout(ctx, origin_monitor, layout);
+}
+
+pub fn send<A>(ctx: &Context<A>, window: Window, monitor: Monitor)
+where
+    A: Allocator + Copy,
+{
+    let origin_monitor = get_monitor_with_window(ctx, window);
+    if origin_monitor == monitor {
+        return;
     }
 
-    // Borrow checker couldn't figure this out.
-    drop(windows_rect);
-    drop(windows_client_rect);
+    // Window lands at the bottom of target stack.
+    ctx.cache.move_window_to_monitor(window, monitor);
+
+    let layout = layout_on(ctx, monitor);
+    match layout {
+        Layout::None => {
+            // Nothing to tile with, center the window keeping its size.
+            let bounding_rect = monitor.rect();
+            let rect = window.rect();
+            window.set_rect(Rect {
+                x: bounding_rect.x + (bounding_rect.width - rect.width) / 2,
+                y: bounding_rect.y + (bounding_rect.height - rect.height) / 2,
+                ..rect
+            });
+        }
+        _ => apply_layout(ctx, monitor, layout),
+    }
 
     // Origin layout is out of date now. Re-apply.
     {
@@ -741,12 +1277,32 @@ where
     A: Allocator + Copy,
 {
     let monitor = get_monitor_with_window(ctx, window);
-    let target = get_adjacent_monitor(&ctx, monitor, direction);
-    send(ctx, window, target);
+    if let Some(target) = get_adjacent_monitor(ctx, monitor, direction) {
+        send(ctx, window, target);
+        warp_cursor(ctx, window, monitor);
+    }
 }
 
-pub fn swap_or_send(window: Window, direction: Direction) {
-    todo!()
+/// Swaps `window` with adjacent tile in `direction`. Window already at the edge of its monitor is
+/// sent to adjacent monitor in that direction instead, focus follows it.
+pub fn swap_or_send<A>(ctx: &Context<A>, window: Window, direction: Direction)
+where
+    A: Allocator + Copy,
+{
+    // Navigation settings do not apply here, wrapping around would never let window leave.
+    if let Some(other) = get_adjacent_window_on_monitor(ctx, window, direction) {
+        swap_tiles(ctx, window, other);
+        return;
+    }
+
+    let monitor = get_monitor_with_window(ctx, window);
+    let Some(target) = get_adjacent_monitor(ctx, monitor, direction) else {
+        return;
+    };
+
+    send(ctx, window, target);
+    window.focus();
+    warp_cursor(ctx, window, monitor);
 }
 
 pub fn swap_monitors(ctx: &Context, m1: Monitor, m2: Monitor) {
@@ -761,80 +1317,234 @@ pub fn swap_monitors(ctx: &Context, m1: Monitor, m2: Monitor) {
     }
 }
 
-pub fn get_adjacent_window<A>(ctx: &Context<A>, window: Window, direction: Direction) -> Window
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Navigation {
+    /// Moving past the last window continues from the opposite side of the monitor.
+    pub wrap: bool,
+    /// Moving past the last window continues on the adjacent monitor. Takes precedence over `wrap`.
+    pub cross_monitors: bool,
+}
+
+/// Sets how directional navigation behaves at monitor edges. Both options are off by default.
+pub fn set_navigation<A>(ctx: &Context<A>, navigation: Navigation)
+where
+    A: Allocator + Copy,
+{
+    ctx.cache.set_navigation(navigation);
+}
+
+/// Finds tiled window next to `window` in `direction`, ties go to the most recently focused
+/// window. What happens at the monitor edge depends on `Navigation` settings.
+pub fn get_adjacent_window<A>(
+    ctx: &Context<A>,
+    window: Window,
+    direction: Direction,
+) -> Option<Window>
 where
     A: Allocator + Copy,
 {
+    if let Some(target) = get_adjacent_window_on_monitor(ctx, window, direction) {
+        return Some(target);
+    }
+
+    let navigation = ctx.cache.navigation();
     let origin_rect = window.rect();
     let monitor = get_monitor_with_window(ctx, window);
-    let windows = get_windows_on_monitor(ctx, monitor);
+
+    if navigation.cross_monitors {
+        if let Some(target_monitor) = get_adjacent_monitor(ctx, monitor, direction) {
+            // Every window there lies past origin, this picks the one closest to it.
+            let windows = get_navigation_candidates(ctx, target_monitor);
+            let rects: Vec<Rect, &Arena> =
+                windows.iter().map(|w| w.rect()).collect_with(&ctx.arena);
+            if let Some(target_idx) = find_rect(origin_rect, &rects, direction) {
+                return Some(windows[target_idx]);
+            }
+        }
+    }
+
+    if navigation.wrap {
+        let windows = get_navigation_candidates(ctx, monitor);
+        let rects: Vec<Rect, &Arena> = windows.iter().map(|w| w.rect()).collect_with(&ctx.arena);
+        if let Some(target_idx) = find_rect_wrapping(origin_rect, &rects, direction) {
+            return Some(windows[target_idx]);
+        }
+    }
+
+    None
+}
+
+fn get_adjacent_window_on_monitor<A>(
+    ctx: &Context<A>,
+    window: Window,
+    direction: Direction,
+) -> Option<Window>
+where
+    A: Allocator + Copy,
+{
+    let origin_rect = window.rect();
+    let monitor = get_monitor_with_window(ctx, window);
+    let windows = get_navigation_candidates(ctx, monitor);
     let rects: Vec<Rect, &Arena> = windows.iter().map(|w| w.rect()).collect_with(&ctx.arena);
 
-    let target_idx = find_rect(origin_rect, &rects, direction);
-    windows[target_idx]
+    find_rect(origin_rect, &rects, direction).map(|idx| windows[idx])
 }
 
-pub fn get_adjacent_monitor<A>(ctx: &Context<A>, monitor: Monitor, direction: Direction) -> Monitor
+// Tiled windows on `monitor` ordered from the most recently focused one, `find_rect` resolves ties
+// in favour of earlier rects.
+fn get_navigation_candidates<A>(ctx: &Context<A>, monitor: Monitor) -> Vec<Window, A>
+where
+    A: Allocator + Copy,
+{
+    let history = get_focus_history(ctx);
+    let mut windows = get_tiled_windows_on_monitor(ctx, monitor);
+    windows.sort_by_key(|w| history.iter().position(|h| h == w).unwrap_or(usize::MAX));
+    windows
+}
+
+/// Finds monitor next to `monitor` in `direction`, ties go to the most recently focused monitor.
+pub fn get_adjacent_monitor<A>(
+    ctx: &Context<A>,
+    monitor: Monitor,
+    direction: Direction,
+) -> Option<Monitor>
 where
     A: Allocator + Copy,
 {
     let origin_rect = monitor.rect();
+    // Monitors are already ordered by focus.
     let monitors = get_monitors(ctx);
     let rects: Vec<Rect, &Arena> = monitors.iter().map(|m| m.rect()).collect_with(&ctx.arena);
 
-    let target_idx = find_rect(origin_rect, &rects, direction);
-    monitors[target_idx]
+    find_rect(origin_rect, &rects, direction).map(|idx| monitors[idx])
 }
 
-// FIXME: 2d navigation is not working correctly.
-pub fn find_rect(origin: Rect, rects: &[Rect], direction: Direction) -> usize {
-    let mut closest_index = 0;
-    let mut closest_distance = i32::MAX;
+// Tiles share borders and windows have invisible ones on top of that, edges this close are
+// considered to be at the same distance.
+const EDGE_TOLERANCE: i32 = 10;
+
+/// Finds index of rect closest to `origin` in `direction`. Only rects whose center lies past the
+/// origin edge facing `direction` and which do not reach back past the opposite edge are
+/// considered. Candidates are ranked by distance between facing edges, those within
+/// `EDGE_TOLERANCE` of the closest one are then ranked by perpendicular overlap, remaining ties go
+/// to the earlier rect, so callers order `rects` by recent focus. Rects equal to `origin` are
+/// skipped.
+pub fn find_rect(origin: Rect, rects: &[Rect], direction: Direction) -> Option<usize> {
+    let candidates = || {
+        rects
+            .iter()
+            .enumerate()
+            .filter(|(_, rect)| **rect != origin)
+            .filter_map(|(i, rect)| {
+                let distance = edge_distance(origin, *rect, direction)?;
+                Some((i, distance, perpendicular_overlap(origin, *rect, direction)))
+            })
+    };
+
+    let closest = candidates().map(|(_, distance, _)| distance).min()?;
 
-    for (i, rect) in rects.iter().enumerate() {
-        if rect == &origin {
+    let mut best: Option<(usize, i32)> = None;
+    for (i, distance, overlap) in candidates() {
+        if distance - closest > EDGE_TOLERANCE {
             continue;
         }
+        if best.is_none_or(|(_, best_overlap)| overlap > best_overlap) {
+            best = Some((i, overlap));
+        }
+    }
 
-        let distance = match direction {
-            Direction::Left => origin.x - (rect.x + rect.width),
-            Direction::Right => rect.x - (origin.x + origin.width),
-            Direction::Up => origin.y - (rect.y + rect.height),
-            Direction::Down => rect.y - (origin.y + origin.height),
-        };
+    best.map(|(i, _)| i)
+}
 
-        if distance < 0 {
-            let overlaps = match direction {
-                Direction::Left | Direction::Right => {
-                    rect.y < origin.y + origin.height && rect.y + rect.height > origin.y
-                }
-                Direction::Up | Direction::Down => {
-                    rect.x < origin.x + origin.width && rect.x + rect.width > origin.x
-                }
-            };
+/// Same as `find_rect` but when nothing lies in `direction` search continues from the opposite
+/// side, as if `origin` was moved just past the furthest rect.
+pub fn find_rect_wrapping(origin: Rect, rects: &[Rect], direction: Direction) -> Option<usize> {
+    if let Some(idx) = find_rect(origin, rects, direction) {
+        return Some(idx);
+    }
 
-            if overlaps {
-                let is_bigger = match direction {
-                    Direction::Left => rect.x < origin.x,
-                    Direction::Right => rect.x + rect.width > origin.x + origin.width,
-                    Direction::Up => rect.y < origin.y,
-                    Direction::Down => rect.y + rect.height > origin.y + origin.height,
-                };
+    let bounds = rects.iter().fold(origin, |acc, r| acc.union(r));
+    let wrapped_origin = match direction {
+        Direction::Left => Rect {
+            x: bounds.x + bounds.width,
+            ..origin
+        },
+        Direction::Right => Rect {
+            x: bounds.x - origin.width,
+            ..origin
+        },
+        Direction::Up => Rect {
+            y: bounds.y + bounds.height,
+            ..origin
+        },
+        Direction::Down => Rect {
+            y: bounds.y - origin.height,
+            ..origin
+        },
+    };
 
-                if is_bigger {
-                    return i;
-                }
-            }
-        } else if distance < closest_distance {
-            closest_distance = distance;
-            closest_index = i;
+    // Origin being the closest one means there is nothing else in its row or column.
+    find_rect(wrapped_origin, rects, direction).filter(|idx| rects[*idx] != origin)
+}
+
+// Distance between facing edges, `None` when `rect` does not lie in `direction` from `origin`.
+fn edge_distance(origin: Rect, rect: Rect, direction: Direction) -> Option<i32> {
+    let center = rect.center();
+    // Rects spanning the whole origin along the axis, e.g. a tall master next to a stack, lie
+    // beside it rather than in `direction` even if their center is past the edge.
+    let (in_half_plane, distance) = match direction {
+        Direction::Left => (
+            center.x < origin.x && rect.x + rect.width < origin.x + origin.width,
+            origin.x - (rect.x + rect.width),
+        ),
+        Direction::Right => (
+            center.x > origin.x + origin.width && rect.x > origin.x,
+            rect.x - (origin.x + origin.width),
+        ),
+        Direction::Up => (
+            center.y < origin.y && rect.y + rect.height < origin.y + origin.height,
+            origin.y - (rect.y + rect.height),
+        ),
+        Direction::Down => (
+            center.y > origin.y + origin.height && rect.y > origin.y,
+            rect.y - (origin.y + origin.height),
+        ),
+    };
+
+    // Overlapping edges count as touching.
+    in_half_plane.then_some(distance.max(0))
+}
+
+fn perpendicular_overlap(origin: Rect, rect: Rect, direction: Direction) -> i32 {
+    let overlap = match direction {
+        Direction::Left | Direction::Right => {
+            (origin.y + origin.height).min(rect.y + rect.height) - origin.y.max(rect.y)
         }
+        Direction::Up | Direction::Down => {
+            (origin.x + origin.width).min(rect.x + rect.width) - origin.x.max(rect.x)
+        }
+    };
+    overlap.max(0)
+}
+
+pub fn restore_all<A>(ctx: &Context<A>, monitor: Monitor)
+where
+    A: Allocator + Copy,
+{
+    let windows = get_windows_on_monitor(ctx, monitor);
+    for window in windows.iter().filter(|w| ctx.cache.is_minimized(**w)) {
+        window.restore();
     }
+}
 
-    if closest_distance == i32::MAX {
-        rects.iter().position(|r| r == &origin).unwrap_or(0)
-    } else {
-        closest_index
+pub fn minimize_others<A>(ctx: &Context<A>, window: Window)
+where
+    A: Allocator + Copy,
+{
+    let monitor = get_monitor_with_window(ctx, window);
+    let windows = get_tiled_windows_on_monitor(ctx, monitor);
+    for other in windows.iter().filter(|w| **w != window) {
+        other.minimize();
     }
 }
 
@@ -857,3 +1567,327 @@ where
         trace_result!(res);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, width: i32, height: i32) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    // 2x2 grid on a 1920x1080 monitor: top left, top right, bottom left, bottom right.
+    fn grid() -> [Rect; 4] {
+        [
+            rect(0, 0, 960, 540),
+            rect(960, 0, 960, 540),
+            rect(0, 540, 960, 540),
+            rect(960, 540, 960, 540),
+        ]
+    }
+
+    // Master on the left and three windows stacked on the right.
+    fn stack() -> [Rect; 4] {
+        [
+            rect(0, 0, 960, 1080),
+            rect(960, 0, 960, 360),
+            rect(960, 360, 960, 360),
+            rect(960, 720, 960, 360),
+        ]
+    }
+
+    #[test]
+    fn stack_master_takes_its_ratio_of_width() {
+        let monitor = rect(0, 0, 1920, 1080);
+        let windows = [rect(0, 0, 100, 100); 3];
+        let mut transformed = [Rect::default(); 3];
+        transform_rects_for_stack(monitor, 1.0, 0.6, &windows, &windows, &mut transformed);
+        assert_eq!(
+            transformed,
+            [
+                rect(0, 0, 1152, 1080),
+                rect(1152, 0, 768, 540),
+                rect(1152, 540, 768, 540),
+            ]
+        );
+    }
+
+    #[test]
+    fn grid_neighbours() {
+        let rects = grid();
+        let top_left = rects[0];
+        assert_eq!(find_rect(top_left, &rects, Direction::Right), Some(1));
+        assert_eq!(find_rect(top_left, &rects, Direction::Down), Some(2));
+        assert_eq!(find_rect(top_left, &rects, Direction::Left), None);
+        assert_eq!(find_rect(top_left, &rects, Direction::Up), None);
+
+        let bottom_right = rects[3];
+        assert_eq!(find_rect(bottom_right, &rects, Direction::Left), Some(2));
+        assert_eq!(find_rect(bottom_right, &rects, Direction::Up), Some(1));
+        assert_eq!(find_rect(bottom_right, &rects, Direction::Right), None);
+        assert_eq!(find_rect(bottom_right, &rects, Direction::Down), None);
+    }
+
+    #[test]
+    fn row_picks_nearest_column() {
+        let rects = [
+            rect(0, 0, 640, 1080),
+            rect(640, 0, 640, 1080),
+            rect(1280, 0, 640, 1080),
+        ];
+        assert_eq!(find_rect(rects[0], &rects, Direction::Right), Some(1));
+        assert_eq!(find_rect(rects[2], &rects, Direction::Left), Some(1));
+        assert_eq!(find_rect(rects[1], &rects, Direction::Right), Some(2));
+    }
+
+    #[test]
+    fn stack_layout() {
+        let rects = stack();
+        // Every stacked window overlaps master equally, the first one wins.
+        assert_eq!(find_rect(rects[0], &rects, Direction::Right), Some(1));
+        for i in 1..4 {
+            assert_eq!(find_rect(rects[i], &rects, Direction::Left), Some(0));
+        }
+        assert_eq!(find_rect(rects[1], &rects, Direction::Down), Some(2));
+        assert_eq!(find_rect(rects[2], &rects, Direction::Down), Some(3));
+        assert_eq!(find_rect(rects[3], &rects, Direction::Down), None);
+        assert_eq!(find_rect(rects[2], &rects, Direction::Up), Some(1));
+    }
+
+    #[test]
+    fn uneven_split_prefers_larger_overlap() {
+        let origin = rect(0, 0, 960, 1080);
+        let rects = [rect(960, 720, 960, 360), rect(960, 0, 960, 720)];
+        assert_eq!(find_rect(origin, &rects, Direction::Right), Some(1));
+
+        let origin = rect(960, 0, 960, 300);
+        let rects = [rect(0, 0, 960, 1080), rect(0, 0, 960, 200)];
+        assert_eq!(find_rect(origin, &rects, Direction::Left), Some(0));
+    }
+
+    #[test]
+    fn gaps_between_windows() {
+        let rects = [
+            rect(10, 10, 940, 1060),
+            rect(970, 10, 940, 520),
+            rect(970, 550, 940, 520),
+        ];
+        assert_eq!(find_rect(rects[0], &rects, Direction::Right), Some(1));
+        assert_eq!(find_rect(rects[1], &rects, Direction::Down), Some(2));
+        assert_eq!(find_rect(rects[2], &rects, Direction::Up), Some(1));
+        assert_eq!(find_rect(rects[2], &rects, Direction::Left), Some(0));
+    }
+
+    #[test]
+    fn distance_ranks_before_overlap() {
+        let origin = rect(0, 0, 100, 100);
+        let near = rect(150, 80, 100, 100);
+        let far = rect(400, 0, 100, 100);
+        assert_eq!(find_rect(origin, &[far, near], Direction::Right), Some(1));
+    }
+
+    #[test]
+    fn near_diagonal_rect_wins_over_far_overlapping_one() {
+        let origin = rect(0, 0, 100, 100);
+        let diagonal = rect(110, 300, 100, 100);
+        let far = rect(2000, 0, 100, 100);
+        assert_eq!(
+            find_rect(origin, &[far, diagonal], Direction::Right),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn rect_beside_origin_is_not_in_direction() {
+        let origin = rect(970, 10, 940, 520);
+        let beside = rect(10, 10, 940, 1060);
+        assert_eq!(find_rect(origin, &[beside], Direction::Down), None);
+        let origin = rect(970, 550, 940, 520);
+        assert_eq!(find_rect(origin, &[beside], Direction::Up), None);
+        let origin = rect(0, 10, 500, 400);
+        let beside = rect(0, 500, 1200, 400);
+        assert_eq!(find_rect(origin, &[beside], Direction::Right), None);
+        let origin = rect(700, 10, 500, 400);
+        assert_eq!(find_rect(origin, &[beside], Direction::Left), None);
+    }
+
+    #[test]
+    fn edges_within_tolerance_rank_by_overlap() {
+        let origin = rect(0, 0, 100, 100);
+        let touching = rect(100, 90, 100, 100);
+        let behind_border = rect(108, 0, 100, 100);
+        assert_eq!(
+            find_rect(origin, &[touching, behind_border], Direction::Right),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn ranking_does_not_depend_on_order() {
+        let origin = rect(0, 0, 100, 100);
+        let a = rect(100, 90, 100, 100);
+        let b = rect(108, 50, 100, 100);
+        let c = rect(116, 0, 100, 100);
+        let orders = [
+            [a, b, c],
+            [a, c, b],
+            [b, a, c],
+            [b, c, a],
+            [c, a, b],
+            [c, b, a],
+        ];
+        for rects in orders {
+            let idx = find_rect(origin, &rects, Direction::Right).unwrap();
+            assert_eq!(rects[idx], b);
+        }
+    }
+
+    #[test]
+    fn rects_outside_half_plane_are_skipped() {
+        let origin = rect(100, 100, 200, 200);
+        // Centered on origin, e.g. a floating window on top of it.
+        let on_top = rect(150, 150, 100, 100);
+        // Sticks out to the right but its center is not past origin right edge.
+        let wide = rect(200, 400, 200, 100);
+        assert_eq!(find_rect(origin, &[on_top, wide], Direction::Right), None);
+        assert_eq!(find_rect(origin, &[on_top], Direction::Down), None);
+    }
+
+    #[test]
+    fn origin_is_skipped() {
+        let rects = grid();
+        assert_eq!(find_rect(rects[1], &[rects[1]], Direction::Left), None);
+        assert_eq!(find_rect(rects[1], &rects, Direction::Left), Some(0));
+    }
+
+    #[test]
+    fn empty_candidates() {
+        assert_eq!(find_rect(rect(0, 0, 10, 10), &[], Direction::Up), None);
+        assert_eq!(
+            find_rect_wrapping(rect(0, 0, 10, 10), &[], Direction::Up),
+            None
+        );
+    }
+
+    #[test]
+    fn monitors_side_by_side() {
+        let monitors = [
+            rect(0, 0, 1920, 1080),
+            rect(1920, 0, 2560, 1440),
+            rect(-1280, 200, 1280, 1024),
+        ];
+        assert_eq!(find_rect(monitors[0], &monitors, Direction::Right), Some(1));
+        assert_eq!(find_rect(monitors[0], &monitors, Direction::Left), Some(2));
+        assert_eq!(find_rect(monitors[1], &monitors, Direction::Left), Some(0));
+        assert_eq!(find_rect(monitors[2], &monitors, Direction::Right), Some(0));
+        assert_eq!(find_rect(monitors[0], &monitors, Direction::Up), None);
+    }
+
+    #[test]
+    fn monitor_above_two_others() {
+        let monitors = [
+            rect(0, 0, 1920, 1080),
+            rect(1920, 0, 1920, 1080),
+            rect(1200, -1080, 1920, 1080),
+        ];
+        // Monitor above overlaps the right one more.
+        assert_eq!(find_rect(monitors[2], &monitors, Direction::Down), Some(1));
+        assert_eq!(find_rect(monitors[0], &monitors, Direction::Up), Some(2));
+        assert_eq!(find_rect(monitors[1], &monitors, Direction::Up), Some(2));
+    }
+
+    #[test]
+    fn windows_across_monitors() {
+        let rects = [
+            rect(0, 0, 960, 1080),
+            rect(960, 0, 960, 1080),
+            rect(1920, 0, 1280, 720),
+            rect(1920, 720, 1280, 720),
+        ];
+        assert_eq!(find_rect(rects[1], &rects, Direction::Right), Some(2));
+        assert_eq!(find_rect(rects[3], &rects, Direction::Left), Some(1));
+    }
+
+    #[test]
+    fn wrapping_in_a_row() {
+        let rects = [
+            rect(0, 0, 640, 1080),
+            rect(640, 0, 640, 1080),
+            rect(1280, 0, 640, 1080),
+        ];
+        assert_eq!(
+            find_rect_wrapping(rects[2], &rects, Direction::Right),
+            Some(0)
+        );
+        assert_eq!(
+            find_rect_wrapping(rects[0], &rects, Direction::Left),
+            Some(2)
+        );
+        // Direct neighbour is preferred over wrapping.
+        assert_eq!(
+            find_rect_wrapping(rects[0], &rects, Direction::Right),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn wrapping_in_a_grid_stays_in_row_and_column() {
+        let rects = grid();
+        assert_eq!(
+            find_rect_wrapping(rects[1], &rects, Direction::Right),
+            Some(0)
+        );
+        assert_eq!(
+            find_rect_wrapping(rects[3], &rects, Direction::Right),
+            Some(2)
+        );
+        assert_eq!(
+            find_rect_wrapping(rects[2], &rects, Direction::Down),
+            Some(0)
+        );
+        assert_eq!(find_rect_wrapping(rects[1], &rects, Direction::Up), Some(3));
+    }
+
+    #[test]
+    fn wrapping_stack_column() {
+        let rects = stack();
+        assert_eq!(
+            find_rect_wrapping(rects[3], &rects, Direction::Down),
+            Some(1)
+        );
+        assert_eq!(find_rect_wrapping(rects[1], &rects, Direction::Up), Some(3));
+        assert_eq!(
+            find_rect_wrapping(rects[1], &rects, Direction::Right),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn wrapping_alone_in_column() {
+        let rects = [rect(0, 0, 960, 1080), rect(960, 0, 960, 1080)];
+        assert_eq!(find_rect_wrapping(rects[0], &rects, Direction::Down), None);
+        assert_eq!(find_rect_wrapping(rects[1], &rects, Direction::Up), None);
+    }
+
+    #[test]
+    fn wrapping_across_monitors() {
+        let monitors = [
+            rect(-1280, 200, 1280, 1024),
+            rect(0, 0, 1920, 1080),
+            rect(1920, 0, 2560, 1440),
+        ];
+        assert_eq!(
+            find_rect_wrapping(monitors[2], &monitors, Direction::Right),
+            Some(0)
+        );
+        assert_eq!(
+            find_rect_wrapping(monitors[0], &monitors, Direction::Left),
+            Some(2)
+        );
+    }
+}